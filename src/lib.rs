@@ -69,6 +69,8 @@ extern crate chrono;
 pub use sheet::*;
 pub use workbook::*;
 
+pub mod prelude;
+mod reader;
 pub mod sheet;
 pub mod workbook;
 
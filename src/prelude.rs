@@ -0,0 +1,18 @@
+//! The commonly used items, for a glob import:
+//!
+//! ```
+//! use simple_excel_writer::prelude::*;
+//!
+//! let mut wb = Workbook::create_in_memory();
+//! let mut sheet = wb.create_sheet("Sheet1");
+//! sheet.add_column(Column { width: 30.0 });
+//! wb.write_sheet(&mut sheet, |sw| sw.append_row(row!["Name", 1.0, blank!(2), text!("=raw")]))
+//!     .expect("write excel error!");
+//! ```
+//!
+//! Unlike `use simple_excel_writer::*`, this leaves out helpers such as `escape_xml`,
+//! `validate_name` and `SharedStrings`.
+
+pub use crate::{blank, row, text};
+pub use sheet::{AutoFilter, CellValue, Column, ColumnType, Currency, PageMargins, Row, Sheet, SheetWriter, ToCellValue};
+pub use workbook::{CalcMode, ProgressEvent, StyleRegistry, Workbook};
@@ -0,0 +1,192 @@
+//! A minimal worksheet reader, just enough to read back the cell values of a written file, see
+//! `Workbook::read_sheet_values`. It scans the XML for the few elements it needs rather than
+//! parsing it properly, so it's only meant for files written by this crate or similar.
+
+use std::io::{Cursor, Error, ErrorKind, Read, Result};
+
+pub(crate) fn read_sheet_values(bytes: &[u8], sheet_name: &str) -> Result<Vec<Vec<String>>> {
+    let mut archive = zip::ZipArchive::new(Cursor::new(bytes))?;
+
+    let workbook = read_part(&mut archive, "xl/workbook.xml")?;
+    let rel_id = blocks(&workbook, "sheet")
+        .into_iter()
+        .find(|(tag, _)| attr(tag, "name").map(|n| unescape(&n)).as_deref() == Some(sheet_name))
+        .and_then(|(tag, _)| attr(tag, "r:id"))
+        .ok_or_else(|| Error::new(ErrorKind::NotFound, format!("no sheet named {:?}", sheet_name)))?;
+    let rels = read_part(&mut archive, "xl/_rels/workbook.xml.rels")?;
+    let target = blocks(&rels, "Relationship")
+        .into_iter()
+        .find(|(tag, _)| attr(tag, "Id").as_ref() == Some(&rel_id))
+        .and_then(|(tag, _)| attr(tag, "Target"))
+        .ok_or_else(|| invalid(format!("no relationship {}", rel_id)))?;
+    let path = match target.strip_prefix('/') {
+        Some(absolute) => absolute.to_string(),
+        None => format!("xl/{}", target),
+    };
+
+    let shared_strings = if archive.by_name("xl/sharedStrings.xml").is_ok() {
+        let xml = read_part(&mut archive, "xl/sharedStrings.xml")?;
+        blocks(&xml, "si").into_iter().map(|(_, si)| text(si)).collect()
+    } else {
+        Vec::new()
+    };
+
+    let sheet = read_part(&mut archive, &path)?;
+    let mut grid: Vec<Vec<String>> = Vec::new();
+    let mut row_index = 0;
+    for (row_tag, row) in blocks(&sheet, "row") {
+        row_index = match attr(row_tag, "r") {
+            Some(r) => r.parse().map_err(|_| invalid(format!("invalid row number {}", r)))?,
+            None => row_index + 1,
+        };
+        let mut col_index = 0;
+        for (tag, content) in blocks(row, "c") {
+            col_index = match attr(tag, "r") {
+                Some(r) => column_number(&r).ok_or_else(|| invalid(format!("invalid cell reference {}", r)))?,
+                None => col_index + 1,
+            };
+            let value = cell_text(tag, content, &shared_strings)?;
+            if grid.len() < row_index {
+                grid.resize(row_index, Vec::new());
+            }
+            let cells = &mut grid[row_index - 1];
+            if cells.len() < col_index {
+                cells.resize(col_index, String::new());
+            }
+            cells[col_index - 1] = value;
+        }
+    }
+    Ok(grid)
+}
+
+fn invalid(msg: String) -> Error {
+    Error::new(ErrorKind::InvalidData, msg)
+}
+
+fn read_part(archive: &mut zip::ZipArchive<Cursor<&[u8]>>, name: &str) -> Result<String> {
+    let mut file = archive
+        .by_name(name)
+        .map_err(|_| invalid(format!("missing part {}", name)))?;
+    let mut xml = String::new();
+    file.read_to_string(&mut xml)?;
+    Ok(xml)
+}
+
+/// The text of a cell: the shared string for `t="s"`, `TRUE`/`FALSE` for booleans and the
+/// stored value otherwise. Formulas without a cached value give an empty string.
+fn cell_text(tag: &str, content: &str, shared_strings: &[String]) -> Result<String> {
+    let value = blocks(content, "v").first().map(|(_, v)| unescape(v));
+    let text = match (attr(tag, "t").as_deref(), value) {
+        (Some("inlineStr"), _) => blocks(content, "is").first().map(|(_, is)| text(is)).unwrap_or_default(),
+        (Some("s"), Some(v)) => v
+            .parse::<usize>()
+            .ok()
+            .and_then(|i| shared_strings.get(i).cloned())
+            .ok_or_else(|| invalid(format!("invalid shared string index {}", v)))?,
+        (Some("b"), Some(v)) => if v == "1" { "TRUE" } else { "FALSE" }.to_string(),
+        (_, Some(v)) => v,
+        (_, None) => String::new(),
+    };
+    Ok(text)
+}
+
+/// The text of a string item (`<si>` or `<is>`), joining its runs and leaving out phonetic
+/// readings.
+fn text(item: &str) -> String {
+    let mut item = item.to_string();
+    while let Some(start) = item.find("<rPh") {
+        match item[start..].find("</rPh>") {
+            Some(end) => item.replace_range(start..start + end + "</rPh>".len(), ""),
+            None => break,
+        }
+    }
+    blocks(&item, "t").into_iter().map(|(_, t)| unescape(t)).collect()
+}
+
+/// The elements named `name` in `xml`, as their start tag and their content. Elements with
+/// the same name must not be nested.
+fn blocks<'a>(xml: &'a str, name: &str) -> Vec<(&'a str, &'a str)> {
+    let open = format!("<{}", name);
+    let close = format!("</{}>", name);
+    let mut found = Vec::new();
+    let mut rest = xml;
+    while let Some(start) = rest.find(&open) {
+        rest = &rest[start..];
+        let name_end = open.len();
+        let is_match = rest[name_end..].starts_with(|c: char| c == '>' || c == '/' || c.is_whitespace());
+        let tag_end = match rest.find('>') {
+            Some(i) => i + 1,
+            None => break,
+        };
+        if !is_match {
+            rest = &rest[name_end..];
+            continue;
+        }
+        let tag = &rest[..tag_end];
+        if tag.ends_with("/>") {
+            found.push((tag, ""));
+            rest = &rest[tag_end..];
+        } else {
+            let content_end = match rest[tag_end..].find(&close) {
+                Some(i) => tag_end + i,
+                None => break,
+            };
+            found.push((tag, &rest[tag_end..content_end]));
+            rest = &rest[content_end + close.len()..];
+        }
+    }
+    found
+}
+
+fn attr(tag: &str, name: &str) -> Option<String> {
+    let pattern = format!(" {}=\"", name);
+    let start = tag.find(&pattern)? + pattern.len();
+    let len = tag[start..].find('"')?;
+    Some(tag[start..start + len].to_string())
+}
+
+/// The 1-based column of a cell reference such as `AB12`.
+fn column_number(reference: &str) -> Option<usize> {
+    let letters = reference.trim_end_matches(|c: char| c.is_ascii_digit());
+    if letters.is_empty() || !letters.chars().all(|c| c.is_ascii_uppercase()) {
+        return None;
+    }
+    Some(letters.bytes().fold(0, |n, b| n * 26 + (b - b'A' + 1) as usize))
+}
+
+pub(crate) fn unescape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let end = match rest.find(';') {
+            Some(end) => end,
+            None => break,
+        };
+        let decoded = match &rest[1..end] {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            entity => match entity.strip_prefix("#x") {
+                Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                None => entity.strip_prefix('#').and_then(|dec| dec.parse().ok()),
+            }
+            .and_then(std::char::from_u32),
+        };
+        match decoded {
+            Some(c) => {
+                out.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
@@ -21,6 +21,17 @@ macro_rules! blank {
     }};
 }
 
+/// Creates a literal string cell, even if the text starts with `=`.
+///
+/// `&str` and `String` values starting with `=` are written as formulas; use this macro (or
+/// construct `CellValue::String` directly) for text that merely looks like one.
+#[macro_export]
+macro_rules! text {
+    ($x:expr) => {{
+        CellValue::String(($x).to_string())
+    }};
+}
+
 pub struct AutoFilter {
     pub start_col: String,
     pub end_col: String,
@@ -28,10 +39,144 @@ pub struct AutoFilter {
     pub end_row: usize
 }
 
-impl ToString for AutoFilter {
-    fn to_string(&self) -> String {
-        format!("{}{}:{}{}", self.start_col, self.start_row, self.end_col, self.end_row)
+impl std::fmt::Display for AutoFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}{}:{}{}", self.start_col, self.start_row, self.end_col, self.end_row)
+    }
+}
+
+/// Workbook-wide settings controlling how worksheet XML is serialized.
+#[derive(Default, Clone, Copy)]
+pub(crate) struct WriteOptions {
+    /// Omit the cosmetic newlines and indentation.
+    pub compact: bool,
+    /// Write booleans as `TRUE`/`FALSE` string cells instead of `t="b"` cells.
+    pub booleans_as_text: bool,
+    /// Leave out the `r` attribute of rows and cells that directly follow the previous one.
+    pub omit_refs: bool,
+    /// Write `t="n"` on number cells instead of relying on it being the default type.
+    pub explicit_number_type: bool,
+    /// The cell format of numbers written without one.
+    pub default_number_format: Option<u16>,
+}
+
+impl WriteOptions {
+    fn newline(&self) -> &'static str {
+        if self.compact {
+            ""
+        } else {
+            "\n"
+        }
+    }
+}
+
+/// Printed page margins, in inches.
+pub struct PageMargins {
+    pub left: f64,
+    pub right: f64,
+    pub top: f64,
+    pub bottom: f64,
+    pub header: f64,
+    pub footer: f64,
+}
+
+impl Default for PageMargins {
+    /// Excel's "Normal" margins.
+    fn default() -> Self {
+        PageMargins {
+            left: 0.7,
+            right: 0.7,
+            top: 0.75,
+            bottom: 0.75,
+            header: 0.3,
+            footer: 0.3,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum PaneState {
+    Split,
+    Frozen,
+}
+
+impl PaneState {
+    fn as_str(&self) -> &'static str {
+        match self {
+            PaneState::Split => "split",
+            PaneState::Frozen => "frozen",
+        }
+    }
+}
+
+/// The `<pane>` of the sheet view. For split panes the offsets are in twips (1/20 point), for
+/// frozen panes they are the number of frozen columns and rows.
+struct Pane {
+    x_split: f64,
+    y_split: f64,
+    state: PaneState,
+    /// The cell the bottom-right pane of frozen panes is scrolled to, if not the first one.
+    top_left_cell: Option<String>,
+}
+
+impl Pane {
+    /// The pane holding the cursor: the bottom-right one when split in both directions.
+    fn active_pane(&self) -> &'static str {
+        match (self.x_split > 0.0, self.y_split > 0.0) {
+            (true, true) => "bottomRight",
+            (true, false) => "topRight",
+            (false, true) => "bottomLeft",
+            (false, false) => "topLeft",
+        }
+    }
+
+    /// The top-left cell of the bottom-right pane of frozen panes.
+    fn first_unfrozen_cell(&self) -> String {
+        ref_id(self.x_split as usize + 1, self.y_split as usize + 1)
     }
+
+    /// The `<selection>` elements for frozen panes, one per pane besides the top-left one,
+    /// each selecting that pane's first cell. Without them Excel leaves the cursor in the
+    /// frozen area.
+    fn write_selections(&self, writer: &mut dyn Write) -> Result<()> {
+        let cols = self.x_split as usize;
+        let rows = self.y_split as usize;
+        let mut panes = Vec::new();
+        if cols > 0 {
+            panes.push(("topRight", ref_id(cols + 1, 1)));
+        }
+        if rows > 0 {
+            panes.push(("bottomLeft", ref_id(1, rows + 1)));
+        }
+        if cols > 0 && rows > 0 {
+            panes.push(("bottomRight", self.first_unfrozen_cell()));
+        }
+        for (pane, cell) in panes {
+            write!(
+                writer,
+                "<selection pane=\"{}\" activeCell=\"{}\" sqref=\"{}\"/>",
+                pane, cell, cell
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// An external link on a cell, see `SheetWriter::add_hyperlink`. The URL goes into the
+/// sheet's relationships part, referenced by `rId{index + 1}`.
+struct Hyperlink {
+    cell: String,
+    url: String,
+    display: Option<String>,
+    tooltip: Option<String>,
+}
+
+/// The sheet's `<printOptions>`, only written if any of them is set.
+#[derive(Default)]
+struct PrintOptions {
+    horizontal_centered: bool,
+    vertical_centered: bool,
+    grid_lines: bool,
 }
 
 #[derive(Default)]
@@ -40,9 +185,39 @@ pub struct Sheet {
     pub name: String,
     pub columns: Vec<Column>,
     max_row_index: usize,
+    max_col_index: usize,
     pub calc_chain: Vec<String>,
     pub merged_cells: Vec<MergedCell>,
-    pub auto_filter: Option<AutoFilter>
+    pub auto_filter: Option<AutoFilter>,
+    pub page_margins: PageMargins,
+    row_breaks: Vec<usize>,
+    col_breaks: Vec<usize>,
+    pane: Option<Pane>,
+    shared_formula_count: usize,
+    inline_strings: bool,
+    hyperlinks: Vec<Hyperlink>,
+    base_col_width: Option<u32>,
+    print_options: PrintOptions,
+    data_bars: Vec<(String, u32)>,
+    number_as_text_ranges: Vec<String>,
+    text_columns: Vec<usize>,
+    last_written_row: usize,
+    print_title_rows: usize,
+    /// Header row an auto-filter over the used range starts at, set by `make_report`.
+    report_filter_row: Option<usize>,
+    range_styles: Vec<RangeStyle>,
+    /// Cell formats of whole columns, as 1-based column and format, see `set_column_style`.
+    column_styles: Vec<(usize, u16)>,
+    extensions: Vec<String>,
+    pub(crate) options: WriteOptions,
+}
+
+/// A cell format for the numbers in a range, see `SheetWriter::style_range`. `start` and
+/// `end` are 1-based `(column, row)` pairs.
+struct RangeStyle {
+    start: (usize, usize),
+    end: (usize, usize),
+    format: u16,
 }
 
 #[derive(Default)]
@@ -51,6 +226,11 @@ pub struct Row {
     row_index: usize,
     max_col_index: usize,
     calc_chain: Vec<String>,
+    height: Option<f64>,
+    /// The row's default cell format, see `SheetWriter::append_row_with_style`.
+    style: Option<u16>,
+    /// Whether the row directly follows the last written row of the sheet.
+    sequential: bool,
 }
 
 pub struct Cell {
@@ -67,9 +247,56 @@ pub struct Column {
     pub width: f32,
 }
 
+/// Maximum digit width, in pixels, of Calibri 11 at 96 DPI.
+const MAX_DIGIT_WIDTH: f32 = 7.0;
+
+impl Column {
+    /// Creates a column wide enough to show `n` digits of Calibri 11, Excel's usual default
+    /// font. The workbooks this crate writes use Calibri 12, whose digits are wider, so in them
+    /// these columns come out wider in pixels than computed here.
+    ///
+    /// Uses the conversion from the OOXML spec (ECMA-376 18.3.1.13), which adds 5 pixels of
+    /// cell padding and rounds down to 1/256 of a character:
+    /// `width = Truncate((n * MDW + 5) / MDW * 256) / 256`, where MDW is 7 pixels.
+    pub fn from_chars(n: u32) -> Column {
+        let px = n as f32 * MAX_DIGIT_WIDTH + 5.0;
+        Column::from_pixels_f32(px)
+    }
+
+    /// Creates a column `px` pixels wide (at 96 DPI), padding included, assuming Calibri
+    /// 11 like `from_chars`.
+    ///
+    /// `width = Truncate(px / MDW * 256) / 256`, where MDW is 7 pixels. Excel's default column
+    /// for Calibri 11, 64 pixels, becomes `9.140625`.
+    pub fn from_pixels(px: u32) -> Column {
+        Column::from_pixels_f32(px as f32)
+    }
+
+    fn from_pixels_f32(px: f32) -> Column {
+        Column {
+            width: (px / MAX_DIGIT_WIDTH * 256.0).trunc() / 256.0,
+        }
+    }
+}
+
+/// How the cells of a column are written, see `Sheet::set_column_type`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ColumnType {
+    /// Each cell is written according to its value (the default).
+    General,
+    /// Numbers (including currency amounts) and booleans are written as text, so the column holds
+    /// strings only.
+    Text,
+}
+
 #[derive(Clone)]
 pub enum CellValue {
     Bool(bool),
+    /// A number, written as the shortest decimal that reads back as the same `f64`: `700.0`
+    /// becomes `700`, `0.1` stays `0.1`, and `0.1 + 0.2` is written as `0.30000000000000004`
+    /// since that's a different value than `0.3`. Digits are never rounded away; Excel shows
+    /// at most 15 significant digits, so the last one displays as `0.3`. How many decimals are
+    /// shown is up to the cell's number format, not the written value.
     Number(f64),
     NumberFormatted((f64, u16)),
     #[cfg(feature = "chrono")]
@@ -77,9 +304,37 @@ pub enum CellValue {
     #[cfg(feature = "chrono")]
     Datetime(f64),
     String(String),
+    /// A formula whose result is a string, written with `t="str"`.
     Formula(String),
+    /// A formula whose result is a number, written without a type attribute so Excel treats
+    /// the result as numeric.
+    NumericFormula(String),
+    /// An array (CSE) formula, written as `<f t="array" ref="..">`. `range` is the range the
+    /// result spills over, e.g. `A1:A3`. Give the formula without the surrounding `{}`.
+    ArrayFormula { formula: String, range: String },
     Blank(usize),
+    /// An empty cell that is written, with the cell format at the given index, e.g. to fill a
+    /// spacer row. Unlike `Blank`, it takes up a single column.
+    StyledBlank(u16),
     SharedString(String),
+    /// Text with a phonetic reading (furigana) for Japanese, shown above the text and used by
+    /// Excel's phonetic sorting. Goes into the shared string table like `String` when the
+    /// workbook uses one, and is written as an inline string otherwise.
+    Phonetic { base: String, ruby: String },
+    /// A date or date and time as ISO 8601 text, e.g. `2012-11-10` or `2012-11-10T15:17:39`,
+    /// written as a date cell (`t="d"`) instead of a serial number. Shown with the same
+    /// formats as `Date` and `Datetime`. Writing the row fails if the text isn't of the form
+    /// `YYYY-MM-DD[THH:MM[:SS[.fff]]][Z]`.
+    IsoDate(String),
+    /// An amount of money in the currency with the ISO 4217 `code`, see `Currency`. When the
+    /// row is appended, the matching number format is registered in the workbook and the cell
+    /// becomes a `NumberFormatted`.
+    Currency { amount: f64, code: String },
+    /// A complete `<c>...</c>` element, written verbatim in place of the cell. It still takes
+    /// up one column. This is an escape hatch for cells this crate doesn't model: the caller
+    /// must provide the `r` attribute matching the cell's position (see `ref_id`), escape any
+    /// text, and make sure the result is valid XML; nothing is checked.
+    Raw(String),
 }
 
 pub struct SheetWriter<'a, 'b>
@@ -89,6 +344,7 @@ where
     sheet: &'a mut Sheet,
     writer: &'b mut Vec<u8>,
     shared_strings: &'b mut crate::SharedStrings,
+    cell_formats: Option<&'b mut crate::workbook::CellFormats>,
 }
 
 pub trait ToCellValue {
@@ -113,6 +369,9 @@ impl ToCellValue for f64 {
     }
 }
 
+/// Text starting with `=` becomes a `CellValue::Formula`, anything else a `CellValue::String`.
+/// This only applies to the conversion from `String` and `&str`; see `text!` for writing such
+/// text literally.
 impl ToCellValue for String {
     fn to_cell_value(&self) -> CellValue {
         if self.starts_with('=') {
@@ -122,7 +381,8 @@ impl ToCellValue for String {
     }
 }
 
-impl<'a> ToCellValue for &'a str {
+/// Same as for `String`: a leading `=` makes it a formula.
+impl ToCellValue for &str {
     fn to_cell_value(&self) -> CellValue {
         if self.starts_with('=') {
             return CellValue::Formula(self.to_string());
@@ -131,6 +391,95 @@ impl<'a> ToCellValue for &'a str {
     }
 }
 
+/// An amount of money for a cell, e.g. `row![Currency::usd(19.99), Currency::eur(17.5)]`. The
+/// cell holds the number, displayed with the currency's symbol (`$19.99`, `€17.50`), or the
+/// code after the amount for currencies without a known symbol (`12.00 CHF`). Each currency's
+/// number format is added to the workbook once, when the first row using it is appended.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Currency<'a> {
+    pub amount: f64,
+    pub code: &'a str,
+}
+
+impl Currency<'static> {
+    pub fn usd(amount: f64) -> Self {
+        Currency { amount, code: "USD" }
+    }
+
+    pub fn eur(amount: f64) -> Self {
+        Currency { amount, code: "EUR" }
+    }
+
+    pub fn gbp(amount: f64) -> Self {
+        Currency { amount, code: "GBP" }
+    }
+
+    pub fn jpy(amount: f64) -> Self {
+        Currency { amount, code: "JPY" }
+    }
+}
+
+/// The number format code for amounts in the currency with the ISO 4217 `code`.
+fn currency_format(code: &str) -> String {
+    match code.to_ascii_uppercase().as_str() {
+        "USD" => "\"$\"#,##0.00".to_string(),
+        "EUR" => "\"€\"#,##0.00".to_string(),
+        "GBP" => "\"£\"#,##0.00".to_string(),
+        "JPY" => "\"¥\"#,##0".to_string(),
+        other => format!("#,##0.00\" {}\"", other.replace('"', "")),
+    }
+}
+
+/// Same as for `String`, so that borrowed strings, e.g. from iterating a `Vec<String>`, don't
+/// need to be cloned.
+impl ToCellValue for &String {
+    fn to_cell_value(&self) -> CellValue {
+        (*self).to_cell_value()
+    }
+}
+
+/// Same as for `&str`.
+impl ToCellValue for &&str {
+    fn to_cell_value(&self) -> CellValue {
+        (**self).to_cell_value()
+    }
+}
+
+/// A one-character string cell. Unlike text, `'='` stays a string, as a lone `=` is no formula.
+impl ToCellValue for char {
+    fn to_cell_value(&self) -> CellValue {
+        CellValue::String(self.to_string())
+    }
+}
+
+impl<'a> ToCellValue for Currency<'a> {
+    fn to_cell_value(&self) -> CellValue {
+        CellValue::Currency {
+            amount: self.amount,
+            code: self.code.to_owned(),
+        }
+    }
+}
+
+/// The maximum number of characters Excel allows in a cell.
+pub const MAX_STRING_LEN: usize = 32767;
+
+/// Excel stores numbers as f64, which can't hold 128-bit integers exactly (anything above
+/// 2^53 loses precision). They are therefore written as string cells holding the decimal
+/// digits, never as numbers.
+impl ToCellValue for u128 {
+    fn to_cell_value(&self) -> CellValue {
+        CellValue::String(self.to_string())
+    }
+}
+
+/// Written as a string cell, see the `u128` implementation.
+impl ToCellValue for i128 {
+    fn to_cell_value(&self) -> CellValue {
+        CellValue::String(self.to_string())
+    }
+}
+
 impl ToCellValue for () {
     fn to_cell_value(&self) -> CellValue {
         CellValue::Blank(1)
@@ -148,6 +497,17 @@ impl ToCellValue for chrono::NaiveDateTime {
     }
 }
 
+/// Written as the date and time shown in the value's own time zone (`naive_local`), as Excel
+/// has no time zones: a `DateTime<Utc>` gives the UTC time and a `DateTime<Local>` the local
+/// time. The offset itself is dropped; convert with `with_timezone` first to show the time in
+/// another zone.
+#[cfg(feature = "chrono")]
+impl<Tz: chrono::TimeZone> ToCellValue for chrono::DateTime<Tz> {
+    fn to_cell_value(&self) -> CellValue {
+        self.naive_local().to_cell_value()
+    }
+}
+
 #[cfg(feature = "chrono")]
 impl ToCellValue for chrono::NaiveDate {
     fn to_cell_value(&self) -> CellValue {
@@ -159,6 +519,29 @@ impl ToCellValue for chrono::NaiveDate {
     }
 }
 
+/// A date with a custom number format, e.g. one added with
+/// `add_cust_number_format("yyyy-mm-dd".to_string())`, instead of the built-in `m/d/yy`.
+#[cfg(feature = "chrono")]
+impl ToCellValue for (chrono::NaiveDate, u16) {
+    fn to_cell_value(&self) -> CellValue {
+        match self.0.to_cell_value() {
+            CellValue::Date(n) => CellValue::NumberFormatted((n, self.1)),
+            _ => unreachable!(),
+        }
+    }
+}
+
+/// A datetime with a custom number format instead of the built-in `m/d/yy h:mm`.
+#[cfg(feature = "chrono")]
+impl ToCellValue for (chrono::NaiveDateTime, u16) {
+    fn to_cell_value(&self) -> CellValue {
+        match self.0.to_cell_value() {
+            CellValue::Datetime(n) => CellValue::NumberFormatted((n, self.1)),
+            _ => unreachable!(),
+        }
+    }
+}
+
 impl Row {
     pub fn new() -> Row {
         Row {
@@ -185,7 +568,9 @@ impl Row {
     {
         let value = value.to_cell_value();
         match &value {
-            CellValue::Formula(f) => {
+            CellValue::Formula(f)
+            | CellValue::NumericFormula(f)
+            | CellValue::ArrayFormula { formula: f, .. } => {
                 self.calc_chain.push(f.to_owned());
                 self.max_col_index += 1;
                 self.cells.push(Cell {
@@ -204,31 +589,66 @@ impl Row {
         }
     }
 
+    /// Leaves the next `cols` columns empty; the next cell added goes after them.
+    /// Equivalent to adding `blank!(cols)`.
     pub fn add_empty_cells(&mut self, cols: usize) {
         self.max_col_index += cols
     }
 
+    /// Alias of `add_empty_cells`, e.g. `row.add_cell("A"); row.skip(1); row.add_cell("C");`
+    /// writes cells A1 and C1.
+    pub fn skip(&mut self, cols: usize) {
+        self.add_empty_cells(cols)
+    }
+
+    /// Appends the cells of `row` after this row's last column, keeping their layout: blanks
+    /// and skips in `row`, including trailing ones, shift the following cells as they would
+    /// in `row` itself.
     pub fn join(&mut self, row: Row) {
+        let offset = self.max_col_index;
         for cell in row.cells.into_iter() {
-            self.inner_add_cell(cell)
+            self.cells.push(Cell {
+                column_index: offset + cell.column_index,
+                value: cell.value,
+            });
         }
+        self.max_col_index = offset + row.max_col_index;
+        self.calc_chain.extend(row.calc_chain);
     }
 
-    fn inner_add_cell(&mut self, cell: Cell) {
-        self.max_col_index += 1;
-        self.cells.push(Cell {
-            column_index: self.max_col_index,
-            value: cell.value,
-        })
+    pub fn write(&mut self, writer: &mut dyn Write) -> Result<()> {
+        self.write_with(writer, &WriteOptions::default())
     }
 
-    pub fn write(&mut self, writer: &mut dyn Write) -> Result<()> {
-        let head = format!("<row r=\"{}\">\n", self.row_index);
+    fn write_with(&mut self, writer: &mut dyn Write, options: &WriteOptions) -> Result<()> {
+        let nl = options.newline();
+        // cell and row references may be left out where they follow the previous ones
+        let mut head = if options.omit_refs && self.sequential {
+            "<row".to_string()
+        } else {
+            format!("<row r=\"{}\"", self.row_index)
+        };
+        // spans tells readers which columns the row occupies before they parse its cells
+        let columns = self.cells.iter().map(|c| c.column_index);
+        if let (Some(min), Some(max)) = (columns.clone().min(), columns.max()) {
+            head.push_str(&format!(" spans=\"{}:{}\"", min, max));
+        }
+        if let Some(style) = self.style {
+            head.push_str(&format!(" s=\"{}\" customFormat=\"1\"", style));
+        }
+        if let Some(ht) = self.height {
+            head.push_str(&format!(" ht=\"{}\" customHeight=\"1\"", ht));
+        }
+        head.push('>');
+        head.push_str(nl);
         writer.write_all(head.as_bytes())?;
+        let mut prev_column = 0;
         for c in self.cells.iter() {
-            c.write(self.row_index, writer)?;
+            let omit_ref = options.omit_refs && c.column_index == prev_column + 1;
+            c.write(self.row_index, omit_ref, options, writer)?;
+            prev_column = c.column_index;
         }
-        writer.write_all(b"\n</row>\n")
+        write!(writer, "{}</row>{}", nl, nl)
     }
 
     pub fn replace_strings(mut self, shared: &mut crate::SharedStrings) -> Self {
@@ -238,6 +658,13 @@ impl Row {
         for cell in self.cells.iter_mut() {
             cell.value = match &cell.value {
                 CellValue::String(val) => shared.register(&escape_xml(val)),
+                CellValue::SharedString(idx) => {
+                    shared.add_count();
+                    CellValue::SharedString(idx.to_owned())
+                }
+                CellValue::Phonetic { base, ruby } => {
+                    shared.register_phonetic(&escape_xml(base), &escape_xml(ruby))
+                }
                 x => x.to_owned(),
             };
         }
@@ -251,58 +678,197 @@ impl ToCellValue for CellValue {
     }
 }
 
-fn write_value(cv: &CellValue, ref_id: String, writer: &mut dyn Write) -> Result<()> {
+/// Writes the `<c>` element of a cell. `ref_attr` is its reference attribute, e.g.
+/// ` r="A1"`, or empty to leave it out.
+fn write_value(
+    cv: &CellValue,
+    ref_attr: &str,
+    options: &WriteOptions,
+    writer: &mut dyn Write,
+) -> Result<()> {
     match cv {
+        CellValue::Bool(b) if options.booleans_as_text => {
+            let v = if *b { "TRUE" } else { "FALSE" };
+            write!(writer, "<c{} t=\"str\"><v>{}</v></c>", ref_attr, v)?;
+        }
         CellValue::Bool(b) => {
             let v = if *b { 1 } else { 0 };
-            let s = format!("<c r=\"{}\" t=\"b\"><v>{}</v></c>", ref_id, v);
+            let s = format!("<c{} t=\"b\"><v>{}</v></c>", ref_attr, v);
             writer.write_all(s.as_bytes())?;
         }
-        &CellValue::Number(num) => write_number(&ref_id, num, None, writer)?,
-        &CellValue::NumberFormatted(num) => write_number(&ref_id, num.0, Some(num.1), writer)?,
+        &CellValue::Number(num) => write_number(ref_attr, num, None, options, writer)?,
+        &CellValue::NumberFormatted(num) => write_number(ref_attr, num.0, Some(num.1), options, writer)?,
         #[cfg(feature = "chrono")]
-        &CellValue::Date(num) => write_number(&ref_id, num, Some(1), writer)?,
+        &CellValue::Date(num) => write_number(ref_attr, num, Some(1), options, writer)?,
         #[cfg(feature = "chrono")]
-        &CellValue::Datetime(num) => write_number(&ref_id, num, Some(2), writer)?,
+        &CellValue::Datetime(num) => write_number(ref_attr, num, Some(2), options, writer)?,
         CellValue::String(ref s) => {
             let s = format!(
-                "<c r=\"{}\" t=\"str\"><v>{}</v></c>",
-                ref_id,
-                escape_xml(&s)
+                "<c{} t=\"str\"><v>{}</v></c>",
+                ref_attr,
+                escape_xml(s)
             );
             writer.write_all(s.as_bytes())?;
         }
         CellValue::Formula(ref s) => {
             let s = format!(
-                "<c r=\"{}\" t=\"str\"><f>{}</f></c>",
-                ref_id,
-                escape_xml(&s)
+                "<c{} t=\"str\"><f>{}</f></c>",
+                ref_attr,
+                escape_xml(s)
             );
             writer.write_all(s.as_bytes())?;
         }
+        CellValue::NumericFormula(ref s) => {
+            write!(writer, "<c{}><f>{}</f></c>", ref_attr, escape_xml(s))?;
+        }
+        CellValue::ArrayFormula {
+            ref formula,
+            ref range,
+        } => {
+            write!(
+                writer,
+                "<c{}><f t=\"array\" ref=\"{}\">{}</f></c>",
+                ref_attr,
+                escape_xml(range),
+                escape_xml(formula)
+            )?;
+        }
         CellValue::SharedString(ref s) => {
-            let s = format!("<c r=\"{}\" t=\"s\"><v>{}</v></c>", ref_id, s);
+            let s = format!("<c{} t=\"s\"><v>{}</v></c>", ref_attr, s);
             writer.write_all(s.as_bytes())?;
         }
+        CellValue::Phonetic { ref base, ref ruby } => {
+            let base = escape_xml(base);
+            write!(
+                writer,
+                "<c{} t=\"inlineStr\"><is><t>{}</t>{}</is></c>",
+                ref_attr,
+                base,
+                phonetic_run(&base, &escape_xml(ruby))
+            )?;
+        }
+        CellValue::IsoDate(ref s) => {
+            let style = match iso_date_kind(s) {
+                Some(IsoDateKind::Date) => 1,
+                Some(IsoDateKind::DateTime) => 2,
+                None => {
+                    return Err(Error::new(
+                        std::io::ErrorKind::InvalidInput,
+                        format!("invalid ISO 8601 date {:?}", s),
+                    ))
+                }
+            };
+            write!(writer, "<c{} s=\"{}\" t=\"d\"><v>{}</v></c>", ref_attr, style, s)?;
+        }
+        // only reached for rows written without a workbook, see `SheetWriter::resolve_currencies`
+        &CellValue::Currency { amount, .. } => write_number(ref_attr, amount, None, options, writer)?,
+        CellValue::Raw(ref s) => writer.write_all(s.as_bytes())?,
         CellValue::Blank(_) => {}
+        CellValue::StyledBlank(style) => write!(writer, "<c{} s=\"{}\"/>", ref_attr, style)?,
     }
     Ok(())
 }
 
+/// Writes `value` with `f64`'s `Display`, which gives the shortest round-tripping decimal
+/// without exponent, see `CellValue::Number`.
 fn write_number(
-    ref_id: &str,
+    ref_attr: &str,
     value: f64,
     style: Option<u16>,
+    options: &WriteOptions,
     writer: &mut dyn Write,
 ) -> Result<()> {
-    match style {
+    let type_attr = if options.explicit_number_type { r#" t="n""# } else { "" };
+    match style.or(options.default_number_format) {
         Some(style) => write!(
             writer,
-            r#"<c r="{}" s="{}"><v>{}</v></c>"#,
-            ref_id, style, value
+            r#"<c{} s="{}"{}><v>{}</v></c>"#,
+            ref_attr, style, type_attr, value
         ),
-        None => write!(writer, r#"<c r="{}"><v>{}</v></c>"#, ref_id, value),
+        None => write!(writer, r#"<c{}{}><v>{}</v></c>"#, ref_attr, type_attr, value),
+    }
+}
+
+enum IsoDateKind {
+    Date,
+    DateTime,
+}
+
+/// Checks that `s` is an ISO 8601 date (`YYYY-MM-DD`), optionally followed by a time
+/// (`THH:MM`, `THH:MM:SS` or `THH:MM:SS.fff`) and a `Z`.
+fn iso_date_kind(s: &str) -> Option<IsoDateKind> {
+    fn number(s: &str, min: u32, max: u32) -> Option<u32> {
+        if s.is_empty() || !s.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        s.parse().ok().filter(|n| *n >= min && *n <= max)
+    }
+
+    let (date, time) = match s.find('T') {
+        Some(i) => (&s[..i], Some(&s[i + 1..])),
+        None => (s, None),
+    };
+    let parts: Vec<&str> = date.split('-').collect();
+    if parts.len() != 3 || parts[0].len() != 4 || parts[1].len() != 2 || parts[2].len() != 2 {
+        return None;
+    }
+    number(parts[0], 0, 9999)?;
+    number(parts[1], 1, 12)?;
+    number(parts[2], 1, 31)?;
+
+    let time = match time {
+        Some(time) => time.strip_suffix('Z').unwrap_or(time),
+        None => return Some(IsoDateKind::Date),
+    };
+    let (time, fraction) = match time.find('.') {
+        Some(i) => (&time[..i], Some(&time[i + 1..])),
+        None => (time, None),
+    };
+    let parts: Vec<&str> = time.split(':').collect();
+    if parts.len() < 2 || parts.len() > 3 || parts.iter().any(|p| p.len() != 2) {
+        return None;
+    }
+    if fraction.is_some() && parts.len() != 3 {
+        return None;
+    }
+    number(parts[0], 0, 23)?;
+    number(parts[1], 0, 59)?;
+    if let Some(seconds) = parts.get(2) {
+        number(seconds, 0, 59)?;
     }
+    if let Some(fraction) = fraction {
+        number(fraction, 0, u32::MAX)?;
+    }
+    Some(IsoDateKind::DateTime)
+}
+
+/// The `<rPh>` run and `<phoneticPr>` giving the (already escaped) `ruby` reading for the whole
+/// of `base`, which is escaped too. The phonetic font is the default font 0, the only font in
+/// the style sheet.
+pub(crate) fn phonetic_run(base: &str, ruby: &str) -> String {
+    let len = unescape_len(base);
+    format!(
+        "<rPh sb=\"0\" eb=\"{}\"><t>{}</t></rPh><phoneticPr fontId=\"0\"/>",
+        len, ruby
+    )
+}
+
+/// The number of characters of escaped text, counting each entity as one.
+fn unescape_len(escaped: &str) -> usize {
+    let mut len = 0;
+    let mut in_entity = false;
+    for c in escaped.chars() {
+        match c {
+            '&' => in_entity = true,
+            ';' if in_entity => {
+                in_entity = false;
+                len += 1;
+            }
+            _ if in_entity => {}
+            _ => len += 1,
+        }
+    }
+    len
 }
 
 pub fn escape_xml(str: &str) -> String {
@@ -314,8 +880,19 @@ pub fn escape_xml(str: &str) -> String {
 }
 
 impl Cell {
-    fn write(&self, row_index: usize, writer: &mut dyn Write) -> Result<()> {
-        write_value(&self.value, ref_id(self.column_index, row_index), writer)
+    fn write(
+        &self,
+        row_index: usize,
+        omit_ref: bool,
+        options: &WriteOptions,
+        writer: &mut dyn Write,
+    ) -> Result<()> {
+        let ref_attr = if omit_ref {
+            String::new()
+        } else {
+            format!(" r=\"{}\"", ref_id(self.column_index, row_index))
+        };
+        write_value(&self.value, &ref_attr, options, writer)
     }
 }
 
@@ -331,14 +908,22 @@ impl MergedCell {
     }
 }
 
+/// Returns the A1-style reference of a cell from its 1-based column and row, e.g.
+/// `ref_id(28, 3)` is `"AB3"`.
+///
+/// # Panics
+/// If `column_index` is 0.
 pub fn ref_id(column_index: usize, row_index: usize) -> String {
     format!("{}{}", column_letter(column_index), row_index)
 }
 
-/**
- * column_index : 1-based
- */
+/// Returns the letters of a 1-based column index: 1 is `A`, 26 is `Z`, 27 is `AA`, 702 is `ZZ`,
+/// 703 is `AAA` and 16384, the last column Excel supports, is `XFD`.
+///
+/// # Panics
+/// If `column_index` is 0.
 pub fn column_letter(column_index: usize) -> String {
+    assert!(column_index > 0, "column_index is 1-based");
     let mut column_index = (column_index - 1) as isize; // turn to 0-based;
     let single = |n: u8| {
         // n : 0-based
@@ -356,43 +941,354 @@ pub fn column_letter(column_index: usize) -> String {
     String::from_iter(result)
 }
 
+/// Parses an A1-style cell reference such as `AB3` into its 1-based column and row. `$` signs
+/// of absolute references are ignored.
+fn parse_ref(reference: &str) -> Option<(usize, usize)> {
+    let reference = reference.replace('$', "");
+    let digits = reference.find(|c: char| c.is_ascii_digit())?;
+    let (letters, row) = reference.split_at(digits);
+    if letters.is_empty() || !letters.chars().all(|c| c.is_ascii_uppercase()) {
+        return None;
+    }
+    let col = letters.bytes().fold(0, |n, b| n * 26 + (b - b'A' + 1) as usize);
+    match row.parse() {
+        Ok(row) if row > 0 => Some((col, row)),
+        _ => None,
+    }
+}
+
+/// Makes `name` acceptable to Excel as a sheet name, then escapes it for XML:
+/// - the forbidden characters `\ / ? * [ ] :` are replaced with `-`,
+/// - control characters are removed,
+/// - leading and trailing apostrophes are removed,
+/// - `History`, which Excel reserves, becomes `History-`.
+///
+/// The result is empty if nothing is left, e.g. for `''`; sheets created with such a name are
+/// called `Sheet{id}` instead.
 pub fn validate_name(name: &str) -> String {
-    escape_xml(name).replace("/", "-")
+    let name: String = name
+        .chars()
+        .filter(|c| !c.is_control())
+        .map(|c| match c {
+            '\\' | '/' | '?' | '*' | '[' | ']' | ':' => '-',
+            c => c,
+        })
+        .collect();
+    let name = name.trim_matches('\'');
+    if name.eq_ignore_ascii_case("History") {
+        return escape_xml(&format!("{}-", name));
+    }
+    escape_xml(name)
+}
+
+/// `validate_name`, falling back to `Sheet{id}` when nothing is left of `name`, as Excel
+/// rejects workbooks with an empty sheet name.
+pub(crate) fn valid_sheet_name(id: usize, name: &str) -> String {
+    let name = validate_name(name);
+    if name.is_empty() {
+        format!("Sheet{}", id)
+    } else {
+        name
+    }
 }
 
 impl Sheet {
     pub fn new(id: usize, sheet_name: &str) -> Sheet {
         Sheet {
             id,
-            name: validate_name(sheet_name), //sheet_name.to_owned(),//escape_xml(sheet_name),
+            name: valid_sheet_name(id, sheet_name), //sheet_name.to_owned(),//escape_xml(sheet_name),
             ..Default::default()
         }
     }
 
+    /// Writes the strings of this sheet inline (`t="str"`) even if the workbook uses a shared
+    /// string table, e.g. to keep a huge data sheet from growing the table that small lookup
+    /// sheets share.
+    pub fn use_inline_strings(&mut self) {
+        self.inline_strings = true;
+    }
+
+    /// Number of rows written so far, including blank rows.
+    pub fn row_count(&self) -> usize {
+        self.max_row_index
+    }
+
+    /// Highest column (1-based) holding a cell in any row written so far.
+    pub fn column_count(&self) -> usize {
+        self.max_col_index
+    }
+
+    /// The range from `A1` to the last written row and column, e.g. `A1:D100`, as given by
+    /// `row_count` and `column_count`. `None` if no cell has been written yet.
+    pub fn used_range(&self) -> Option<String> {
+        if self.max_row_index == 0 || self.max_col_index == 0 {
+            return None;
+        }
+        Some(format!("A1:{}", ref_id(self.max_col_index, self.max_row_index)))
+    }
+
     /// Adds the "AutoFilter" feature to the specified range of columns and rows (1-indexed).
     /// The arguments are used to construct the range of columns and rows used by the "AutoFilter"
     /// feature. For example: Column 1, Row 1 to Column 2, Row 2 will create the range "A1:B2".
     /// If invalid parameters are provided, the "AutoFilter" is not created.
+    ///
+    /// A sheet has at most one auto-filter, so each call replaces the previous filter,
+    /// including the one of `make_report`; see `clear_auto_filter` to remove it.
     pub fn add_auto_filter(&mut self, start_col: usize, end_col: usize, start_row: usize, end_row: usize) {
         if start_col > 0 && start_row > 0 && start_col <= end_col && start_row <= end_row {
             self.auto_filter = Some(AutoFilter{ start_col: column_letter(start_col),
                                                 end_col: column_letter(end_col),
                                                 start_row, end_row });
+            self.report_filter_row = None;
+        }
+    }
+
+    /// Removes the auto-filter, including the one `make_report` adds when the sheet is written.
+    pub fn clear_auto_filter(&mut self) {
+        self.auto_filter = None;
+        self.report_filter_row = None;
+    }
+
+    /// Sets the printed page margins, in inches. If never called, Excel's default margins
+    /// (0.7/0.7/0.75/0.75/0.3/0.3) are written.
+    pub fn set_margins(&mut self, left: f64, right: f64, top: f64, bottom: f64, header: f64, footer: f64) {
+        self.page_margins = PageMargins {
+            left,
+            right,
+            top,
+            bottom,
+            header,
+            footer,
+        };
+    }
+
+    /// Inserts a manual page break before the given row (1-based), so that `row` starts a new
+    /// printed page. Breaks before the first row are ignored.
+    pub fn add_row_page_break(&mut self, row: usize) {
+        if row > 1 && !self.row_breaks.contains(&row) {
+            self.row_breaks.push(row);
+        }
+    }
+
+    /// Inserts a manual page break before the given column (1-based), so that `col` starts a new
+    /// printed page. Breaks before the first column are ignored.
+    pub fn add_col_page_break(&mut self, col: usize) {
+        if col > 1 && !self.col_breaks.contains(&col) {
+            self.col_breaks.push(col);
+        }
+    }
+
+    /// Shows the values of `range` (e.g. `B2:B20`) as in-cell bars of the ARGB `color`, alpha in
+    /// the high byte (e.g. `0xFF638EC6`), scaled from the range's minimum to its maximum.
+    pub fn add_data_bar(&mut self, range: &str, color: u32) {
+        self.data_bars.push((range.to_owned(), color));
+    }
+
+    /// Centers the printed content on the page horizontally and/or vertically, instead of
+    /// placing it at the top left within the margins.
+    pub fn set_print_centered(&mut self, horizontal: bool, vertical: bool) {
+        self.print_options.horizontal_centered = horizontal;
+        self.print_options.vertical_centered = vertical;
+    }
+
+    /// Prints the cell gridlines. This only affects printing: Excel shows gridlines on screen
+    /// and leaves them out on paper unless this is set.
+    pub fn print_gridlines(&mut self, print: bool) {
+        self.print_options.grid_lines = print;
+    }
+
+    /// Splits the window into movable panes, `x` twips (1/20 of a point) from the left and `y`
+    /// twips from the top. Either may be 0 to split in one direction only; both 0 removes the
+    /// split. Unlike frozen panes, the user can drag the split bars and scroll each pane.
+    pub fn split_panes(&mut self, x: f64, y: f64) {
+        self.pane = if x > 0.0 || y > 0.0 {
+            Some(Pane {
+                x_split: x.max(0.0),
+                y_split: y.max(0.0),
+                state: PaneState::Split,
+                top_left_cell: None,
+            })
+        } else {
+            None
+        };
+    }
+
+    /// Sets how the cells of column `col` (1-based) are written. With `ColumnType::Text`, numbers
+    /// (including currency amounts) and booleans appended to the column become strings, so
+    /// readers that infer a column's type from its values see text throughout, e.g. for IDs that
+    /// are sometimes numeric.
+    /// Formulas and blank cells are left as they are. Applies to rows appended afterwards.
+    pub fn set_column_type(&mut self, col: usize, column_type: ColumnType) {
+        self.text_columns.retain(|&c| c != col);
+        if column_type == ColumnType::Text {
+            self.text_columns.push(col);
         }
     }
 
+    /// Sets the default width of the sheet's columns to `chars` characters of the default
+    /// font's widest digit, written as `baseColWidth` on `<sheetFormatPr>`. Unlike `Column`
+    /// widths this excludes the cell padding, which Excel adds itself. Columns added with
+    /// `add_column` keep their own width.
+    pub fn set_base_col_width(&mut self, chars: u32) {
+        self.base_col_width = Some(chars);
+    }
+
+    /// Freezes the top `rows` rows and the leftmost `cols` columns, so they stay visible while
+    /// scrolling. Either may be 0 to freeze in one direction only; both 0 removes the freeze.
+    /// Replaces any split set with `split_panes`.
+    pub fn freeze_panes(&mut self, rows: usize, cols: usize) {
+        self.pane = if rows > 0 || cols > 0 {
+            Some(Pane {
+                x_split: cols as f64,
+                y_split: rows as f64,
+                state: PaneState::Frozen,
+                top_left_cell: None,
+            })
+        } else {
+            None
+        };
+    }
+
+    /// Same as `freeze_panes`, with the scrolling pane starting at `top_left` (e.g. `F3`)
+    /// instead of the first cell after the frozen rows and columns. Returns an `InvalidInput`
+    /// error if `top_left` isn't a cell reference past the frozen area, or nothing is frozen.
+    pub fn freeze_panes_at(&mut self, rows: usize, cols: usize, top_left: &str) -> Result<()> {
+        match parse_ref(top_left) {
+            Some((col, row)) if (rows > 0 || cols > 0) && col > cols && row > rows => {
+                self.freeze_panes(rows, cols);
+                if let Some(pane) = &mut self.pane {
+                    pane.top_left_cell = Some(ref_id(col, row));
+                }
+                Ok(())
+            }
+            _ => Err(Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("{:?} is not a cell past {} frozen rows and {} columns", top_left, rows, cols),
+            )),
+        }
+    }
+
+    /// Repeats the top `rows` rows at the top of every printed page. 0 removes the print titles.
+    pub fn set_print_title_rows(&mut self, rows: usize) {
+        self.print_title_rows = rows;
+    }
+
+    pub(crate) fn print_title_rows(&self) -> usize {
+        self.print_title_rows
+    }
+
+    /// Sets the sheet up as a report with `header_rows` header rows at the top: the header is
+    /// frozen and repeated on every printed page, and an auto-filter is added from the last
+    /// header row down to the last written row, across all written columns.
+    pub fn make_report(&mut self, header_rows: usize) {
+        self.freeze_panes(header_rows, 0);
+        self.set_print_title_rows(header_rows);
+        self.report_filter_row = if header_rows > 0 { Some(header_rows) } else { None };
+    }
+
+    /// Adds raw extension XML, one or more `<ext uri="...">` elements, to the `<extLst>` at
+    /// the end of the worksheet, e.g. sparklines generated by another tool. The XML is written
+    /// as is, so it must be well-formed.
+    pub fn add_ext(&mut self, xml: &str) {
+        self.extensions.push(xml.to_string());
+    }
+
     pub fn add_column(&mut self, column: Column) {
         self.columns.push(column)
     }
 
+    /// Replaces all columns with ones of the given widths, starting from the first column.
+    pub fn set_column_widths(&mut self, widths: &[f32]) {
+        self.columns = widths.iter().map(|&width| Column { width }).collect();
+    }
+
+    /// Sets the default cell format of column `col` (1-based), written as `style` on its
+    /// `<col>`, which Excel uses for cells of the column that aren't written, e.g. values
+    /// typed in later. Numbers appended to the column without a format of their own get it
+    /// too, while cells written with a format, e.g. a header row, keep theirs; formats set
+    /// with `SheetWriter::style_range` or `append_row_with_style` take precedence. Applies to
+    /// rows appended afterwards.
+    pub fn set_column_style(&mut self, col: usize, format: u16) {
+        if col > 0 {
+            self.column_styles.retain(|&(c, _)| c != col);
+            self.column_styles.push((col, format));
+        }
+    }
+
+    /// Changes the width of an existing column (1-based). Columns that haven't been added are
+    /// ignored.
+    pub fn set_column_width(&mut self, index: usize, width: f32) {
+        if index > 0 {
+            if let Some(column) = self.columns.get_mut(index - 1) {
+                column.width = width;
+            }
+        }
+    }
+
     fn write_row<W>(&mut self, writer: &mut W, mut row: Row) -> Result<()>
     where
         W: Write + Sized,
     {
         self.max_row_index += 1;
         row.row_index = self.max_row_index;
+        row.sequential = row.row_index == self.last_written_row + 1;
+        self.last_written_row = row.row_index;
+        if let Some(cell) = row.cells.last() {
+            self.max_col_index = self.max_col_index.max(cell.column_index);
+        }
         self.calc_chain.append(&mut row.calc_chain);
-        row.write(writer)
+        row.write_with(writer, &self.options)
+    }
+
+    /// Writes `count` rows starting at `start_row`, each holding one cell of the shared formula
+    /// in column `col`. Returns an error if `start_row` has already been written.
+    fn write_shared_formula_rows<W>(
+        &mut self,
+        writer: &mut W,
+        col: usize,
+        start_row: usize,
+        count: usize,
+        formula: &str,
+    ) -> Result<()>
+    where
+        W: Write + Sized,
+    {
+        if col == 0 || start_row <= self.max_row_index {
+            return Err(Error::new(ErrorKind::Other, "invalid shared formula position"));
+        }
+        if count == 0 {
+            return Ok(());
+        }
+        let si = self.shared_formula_count;
+        self.shared_formula_count += 1;
+        let end_row = start_row + count - 1;
+        let nl = self.options.newline();
+        for row_index in start_row..=end_row {
+            write!(writer, "<row r=\"{}\" spans=\"{}:{}\">{}", row_index, col, col, nl)?;
+            if row_index == start_row {
+                write!(
+                    writer,
+                    "<c r=\"{}\"><f t=\"shared\" ref=\"{}:{}\" si=\"{}\">{}</f></c>",
+                    ref_id(col, row_index),
+                    ref_id(col, start_row),
+                    ref_id(col, end_row),
+                    si,
+                    escape_xml(formula)
+                )?;
+            } else {
+                write!(
+                    writer,
+                    "<c r=\"{}\"><f t=\"shared\" si=\"{}\"/></c>",
+                    ref_id(col, row_index),
+                    si
+                )?;
+            }
+            write!(writer, "{}</row>{}", nl, nl)?;
+        }
+        self.max_row_index = end_row;
+        self.last_written_row = end_row;
+        self.max_col_index = self.max_col_index.max(col);
+        Ok(())
     }
 
     fn write_blank_rows(&mut self, rows: usize) {
@@ -400,33 +1296,94 @@ impl Sheet {
     }
 
     fn write_head(&self, writer: &mut dyn Write) -> Result<()> {
-        let header = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+        let header = if self.options.compact {
+            concat!(
+                r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>"#,
+                r#"<worksheet xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main" "#,
+                r#"xmlns:r="http://schemas.openxmlformats.org/officeDocument/2006/relationships">"#
+            )
+        } else {
+            r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
 <worksheet xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main"
         xmlns:r="http://schemas.openxmlformats.org/officeDocument/2006/relationships">
-        "#;
+        "#
+        };
         writer.write_all(header.as_bytes())?;
         /*
                 let dimension = format!("<dimension ref=\"A1:{}{}\"/>", column_letter(self.dimension.columns), self.dimension.rows);
                 writer.write_all(dimension.as_bytes())?;
         */
+        Ok(())
+    }
 
-        if self.columns.is_empty() {
-            return Ok(());
+    fn write_sheet_views(&self, writer: &mut dyn Write) -> Result<()> {
+        let pane = match &self.pane {
+            Some(pane) => pane,
+            None => return Ok(()),
+        };
+        let nl = self.options.newline();
+        write!(writer, "{}<sheetViews><sheetView workbookViewId=\"0\">", nl)?;
+        write!(writer, "<pane")?;
+        if pane.x_split > 0.0 {
+            write!(writer, " xSplit=\"{}\"", pane.x_split)?;
+        }
+        if pane.y_split > 0.0 {
+            write!(writer, " ySplit=\"{}\"", pane.y_split)?;
+        }
+        if pane.state == PaneState::Frozen {
+            let top_left = pane.top_left_cell.clone().unwrap_or_else(|| pane.first_unfrozen_cell());
+            write!(writer, " topLeftCell=\"{}\"", top_left)?;
+        }
+        write!(
+            writer,
+            " activePane=\"{}\" state=\"{}\"/>",
+            pane.active_pane(),
+            pane.state.as_str()
+        )?;
+        if pane.state == PaneState::Frozen {
+            pane.write_selections(writer)?;
         }
+        write!(writer, "</sheetView></sheetViews>{}", nl)
+    }
 
-        writer.write_all(b"\n<cols>\n")?;
-        let mut i = 1;
-        for col in self.columns.iter() {
-            writer.write_all(
-                format!(
-                    "<col min=\"{}\" max=\"{}\" width=\"{}\" customWidth=\"1\"/>\n",
-                    &i, &i, col.width
-                )
-                .as_bytes(),
+    fn write_sheet_format(&self, writer: &mut dyn Write) -> Result<()> {
+        if let Some(chars) = self.base_col_width {
+            let nl = self.options.newline();
+            // defaultRowHeight is required by the schema; 15 points is Excel's own default
+            write!(
+                writer,
+                "{}<sheetFormatPr baseColWidth=\"{}\" defaultRowHeight=\"15\"/>",
+                nl, chars
             )?;
-            i += 1;
         }
-        writer.write_all(b"</cols>\n")
+        Ok(())
+    }
+
+    fn write_cols(&self, writer: &mut dyn Write) -> Result<()> {
+        let last_styled = self.column_styles.iter().map(|&(c, _)| c).max().unwrap_or(0);
+        let count = self.columns.len().max(last_styled);
+        if count == 0 {
+            return Ok(());
+        }
+
+        let nl = self.options.newline();
+        write!(writer, "{}<cols>{}", nl, nl)?;
+        for i in 1..=count {
+            let width = self.columns.get(i - 1).map(|col| col.width);
+            let style = self.column_style(i);
+            if width.is_none() && style.is_none() {
+                continue;
+            }
+            write!(writer, "<col min=\"{}\" max=\"{}\"", i, i)?;
+            if let Some(width) = width {
+                write!(writer, " width=\"{}\" customWidth=\"1\"", width)?;
+            }
+            if let Some(style) = style {
+                write!(writer, " style=\"{}\"", style)?;
+            }
+            write!(writer, "/>{}", nl)?;
+        }
+        write!(writer, "</cols>{}", nl)
     }
 
     fn write_merged_cells(&self, writer: &mut dyn Write) -> Result<()> {
@@ -442,20 +1399,200 @@ impl Sheet {
         Ok(())
     }
 
+    fn write_conditional_formatting(&self, writer: &mut dyn Write) -> Result<()> {
+        for (priority, (range, color)) in (1..).zip(self.data_bars.iter()) {
+            write!(
+                writer,
+                "<conditionalFormatting sqref=\"{}\"><cfRule type=\"dataBar\" priority=\"{}\"><dataBar>\
+                 <cfvo type=\"min\"/><cfvo type=\"max\"/><color rgb=\"{:08X}\"/></dataBar></cfRule>\
+                 </conditionalFormatting>",
+                escape_xml(range),
+                priority,
+                color
+            )?;
+        }
+        Ok(())
+    }
+
+    fn write_hyperlinks(&self, writer: &mut dyn Write) -> Result<()> {
+        if self.hyperlinks.is_empty() {
+            return Ok(());
+        }
+        write!(writer, "<hyperlinks>")?;
+        for (i, link) in (1..).zip(self.hyperlinks.iter()) {
+            write!(writer, "<hyperlink ref=\"{}\" r:id=\"rId{}\"", link.cell, i)?;
+            if let Some(display) = &link.display {
+                write!(writer, " display=\"{}\"", escape_xml(display))?;
+            }
+            if let Some(tooltip) = &link.tooltip {
+                write!(writer, " tooltip=\"{}\"", escape_xml(tooltip))?;
+            }
+            write!(writer, "/>")?;
+        }
+        write!(writer, "</hyperlinks>")
+    }
+
+    /// The sheet's relationships part (`xl/worksheets/_rels/sheetN.xml.rels`), or `None` if
+    /// the sheet doesn't refer to any other part.
+    pub(crate) fn rels_xml(&self) -> Option<Vec<u8>> {
+        if self.hyperlinks.is_empty() {
+            return None;
+        }
+        let mut xml = String::from(concat!(
+            r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>"#,
+            "\n",
+            r#"<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships">"#
+        ));
+        for (i, link) in (1..).zip(self.hyperlinks.iter()) {
+            xml.push_str(&format!(
+                "<Relationship Id=\"rId{}\" Type=\"http://schemas.openxmlformats.org/officeDocument/2006/relationships/hyperlink\" Target=\"{}\" TargetMode=\"External\"/>",
+                i,
+                escape_xml(&link.url)
+            ));
+        }
+        xml.push_str("</Relationships>");
+        Some(xml.into_bytes())
+    }
+
+    fn write_print_options(&self, writer: &mut dyn Write) -> Result<()> {
+        let options = &self.print_options;
+        if !options.horizontal_centered && !options.vertical_centered && !options.grid_lines {
+            return Ok(());
+        }
+        write!(writer, "<printOptions")?;
+        if options.horizontal_centered {
+            write!(writer, " horizontalCentered=\"1\"")?;
+        }
+        if options.vertical_centered {
+            write!(writer, " verticalCentered=\"1\"")?;
+        }
+        if options.grid_lines {
+            write!(writer, " gridLines=\"1\"")?;
+        }
+        write!(writer, "/>")
+    }
+
+    fn write_page_margins(&self, writer: &mut dyn Write) -> Result<()> {
+        let m = &self.page_margins;
+        write!(
+            writer,
+            "<pageMargins left=\"{}\" right=\"{}\" top=\"{}\" bottom=\"{}\" header=\"{}\" footer=\"{}\"/>",
+            m.left, m.right, m.top, m.bottom, m.header, m.footer
+        )
+    }
+
+    fn write_ignored_errors(&self, writer: &mut dyn Write) -> Result<()> {
+        if self.number_as_text_ranges.is_empty() {
+            return Ok(());
+        }
+        write!(
+            writer,
+            "<ignoredErrors><ignoredError sqref=\"{}\" numberStoredAsText=\"1\"/></ignoredErrors>",
+            escape_xml(&self.number_as_text_ranges.join(" "))
+        )
+    }
+
+    fn write_page_breaks(&self, writer: &mut dyn Write) -> Result<()> {
+        // A break's id is the last row/column (1-based) before the break.
+        // `max` spans the whole other dimension: all columns for row breaks and vice versa.
+        let mut write_breaks = |tag: &str, breaks: &[usize], max: usize| -> Result<()> {
+            if breaks.is_empty() {
+                return Ok(());
+            }
+            let mut breaks = breaks.to_vec();
+            breaks.sort_unstable();
+            write!(
+                writer,
+                "<{} count=\"{}\" manualBreakCount=\"{}\">",
+                tag,
+                breaks.len(),
+                breaks.len()
+            )?;
+            for b in breaks {
+                write!(writer, "<brk id=\"{}\" max=\"{}\" man=\"1\"/>", b - 1, max)?;
+            }
+            write!(writer, "</{}>", tag)
+        };
+        write_breaks("rowBreaks", &self.row_breaks, 16383)?;
+        write_breaks("colBreaks", &self.col_breaks, 1048575)
+    }
+
     fn write_data_begin(&self, writer: &mut dyn Write) -> Result<()> {
-        writer.write_all(b"\n<sheetData>\n")
+        let nl = self.options.newline();
+        write!(writer, "{}<sheetData>{}", nl, nl)
     }
 
     fn write_data_end(&self, writer: &mut dyn Write) -> Result<()> {
+        let nl = self.options.newline();
+        write!(writer, "{}</sheetData>{}", nl, nl)
+    }
+
+    fn write_auto_filter(&self, writer: &mut dyn Write) -> Result<()> {
         if let Some(auto_filter) = &self.auto_filter {
-            writer.write_all(format!("\n</sheetData>\n<autoFilter ref=\"{}\"/>\n", auto_filter.to_string()).as_bytes())
-        } else {
-            writer.write_all(b"\n</sheetData>\n")
+            write!(writer, "<autoFilter ref=\"{}\"/>{}", auto_filter, self.options.newline())?;
         }
+        Ok(())
+    }
+
+    /// Converts the values of cells in text columns to strings.
+    fn apply_column_types(&self, mut row: Row) -> Row {
+        if self.text_columns.is_empty() {
+            return row;
+        }
+        for cell in row.cells.iter_mut() {
+            if !self.text_columns.contains(&cell.column_index) {
+                continue;
+            }
+            let text = match cell.value {
+                CellValue::Number(n) | CellValue::NumberFormatted((n, _)) => n.to_string(),
+                CellValue::Currency { amount, .. } => amount.to_string(),
+                CellValue::Bool(b) => if b { "TRUE" } else { "FALSE" }.to_string(),
+                _ => continue,
+            };
+            cell.value = CellValue::String(text);
+        }
+        row
+    }
+
+    /// Gives numbers without a format the one of their range (see `style_range`) or, failing
+    /// that, of their row (see `append_row_with_style`) or column (see `set_column_style`).
+    fn apply_range_styles(&self, mut row: Row) -> Row {
+        let row_index = self.max_row_index + 1;
+        for cell in row.cells.iter_mut() {
+            if let CellValue::Number(n) = cell.value {
+                let col = cell.column_index;
+                let format = self
+                    .range_styles
+                    .iter()
+                    .rev()
+                    .find(|style| {
+                        (style.start.0..=style.end.0).contains(&col)
+                            && (style.start.1..=style.end.1).contains(&row_index)
+                    })
+                    .map(|style| style.format)
+                    .or(row.style)
+                    .or_else(|| self.column_style(col));
+                if let Some(format) = format {
+                    cell.value = CellValue::NumberFormatted((n, format));
+                }
+            }
+        }
+        row
+    }
+
+    fn column_style(&self, col: usize) -> Option<u16> {
+        self.column_styles.iter().find(|&&(c, _)| c == col).map(|&(_, format)| format)
+    }
+
+    fn write_ext_list(&self, writer: &mut dyn Write) -> Result<()> {
+        if self.extensions.is_empty() {
+            return Ok(());
+        }
+        write!(writer, "<extLst>{}</extLst>{}", self.extensions.concat(), self.options.newline())
     }
 
     fn close(&self, writer: &mut dyn Write) -> Result<()> {
-        writer.write_all(b"</worksheet>\n")
+        write!(writer, "</worksheet>{}", self.options.newline())
     }
 }
 
@@ -469,18 +1606,187 @@ impl<'a, 'b> SheetWriter<'a, 'b> {
             sheet,
             writer,
             shared_strings,
+            cell_formats: None,
+        }
+    }
+
+    /// Lets the writer register number formats in the workbook, see `add_cust_number_format`.
+    pub(crate) fn with_cell_formats(
+        mut self,
+        cell_formats: &'b mut crate::workbook::CellFormats,
+    ) -> SheetWriter<'a, 'b> {
+        self.cell_formats = Some(cell_formats);
+        self
+    }
+
+    /// Rejects strings over `MAX_STRING_LEN` characters with an `InvalidInput` error naming the
+    /// cell. Excel would truncate them when opening the file and report it as damaged.
+    fn check_string_lengths(&self, row: &Row) -> Result<()> {
+        for cell in row.cells.iter() {
+            let text = match &cell.value {
+                CellValue::String(s) => s,
+                CellValue::Phonetic { base, .. } => base,
+                _ => continue,
+            };
+            let len = text.chars().count();
+            if len > MAX_STRING_LEN {
+                return Err(Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    format!(
+                        "string in cell {} is {} characters long, Excel allows at most {}",
+                        ref_id(cell.column_index, self.sheet.max_row_index + 1),
+                        len,
+                        MAX_STRING_LEN
+                    ),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn check_iso_dates(&self, row: &Row) -> Result<()> {
+        for cell in row.cells.iter() {
+            if let CellValue::IsoDate(ref s) = cell.value {
+                if iso_date_kind(s).is_none() {
+                    return Err(Error::new(
+                        std::io::ErrorKind::InvalidInput,
+                        format!(
+                            "invalid ISO 8601 date {:?} in cell {}",
+                            s,
+                            ref_id(cell.column_index, self.sheet.max_row_index + 1)
+                        ),
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Replaces `Currency` cells with numbers in the currency's number format, which is added to
+    /// the workbook if needed.
+    fn resolve_currencies(&mut self, mut row: Row) -> Result<Row> {
+        for cell in row.cells.iter_mut() {
+            if let CellValue::Currency { amount, ref code } = cell.value {
+                let format = self.add_cust_number_format(&currency_format(code))?;
+                cell.value = CellValue::NumberFormatted((amount, format));
+            }
+        }
+        Ok(row)
+    }
+
+    /// Registers a custom number format in the workbook while the sheet is being written, for
+    /// formats that depend on the data. Returns the format index to use with
+    /// `CellValue::NumberFormatted`, like `Workbook::add_cust_number_format`, except that a
+    /// pattern that is already registered returns the existing index instead of adding a
+    /// duplicate. Fails for writers created with `SheetWriter::new`, which have no workbook.
+    pub fn add_cust_number_format(&mut self, pattern: &str) -> Result<u16> {
+        match &mut self.cell_formats {
+            Some(cell_formats) => Ok(cell_formats.find_or_add_number_format(pattern)),
+            None => Err(Error::new(ErrorKind::Other, "the sheet writer has no workbook to add formats to")),
         }
     }
 
     pub fn append_row(&mut self, row: Row) -> Result<()> {
+        self.check_string_lengths(&row)?;
+        self.check_iso_dates(&row)?;
+        let row = self.sheet.apply_column_types(row);
+        let row = self.resolve_currencies(row)?;
+        let row = self.sheet.apply_range_styles(row);
+        if !self.shared_strings.used() {
+            if let Some(cell) = row.cells.iter().find(|c| matches!(c.value, CellValue::SharedString(_))) {
+                return Err(Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    format!(
+                        "cell {} refers to a shared string, but the workbook has no shared string table",
+                        ref_id(cell.column_index, self.sheet.max_row_index + 1)
+                    ),
+                ));
+            }
+        }
+        let row = if self.sheet.inline_strings {
+            for cell in row.cells.iter() {
+                if let CellValue::SharedString(_) = cell.value {
+                    self.shared_strings.add_count();
+                }
+            }
+            row
+        } else {
+            row.replace_strings(self.shared_strings)
+        };
+        self.sheet.write_row(self.writer, row)
+    }
+
+    /// Same as `append_row`, with the row's height set to `height` points. Only these rows get
+    /// `customHeight`; rows written with `append_row` keep the default height, which Excel
+    /// adjusts to fit wrapped text.
+    pub fn append_row_with_height(&mut self, mut row: Row, height: f64) -> Result<()> {
+        row.height = Some(height);
+        self.append_row(row)
+    }
+
+    /// Same as `append_row`, with `format` as the row's default cell format (`s` and
+    /// `customFormat` on `<row>`), which Excel uses for the row's cells that aren't written.
+    /// Numbers in the row without a format of their own get it too; cells written with a
+    /// format keep theirs.
+    pub fn append_row_with_style(&mut self, mut row: Row, format: u16) -> Result<()> {
+        row.style = Some(format);
+        self.append_row(row)
+    }
+
+    /// Writes a row of numbers that all use the cell format `format`, as returned by
+    /// `Workbook::add_cust_number_format`, instead of wrapping each value in a `(value, format)`
+    /// tuple.
+    pub fn append_numeric_row(&mut self, values: &[f64], format: u16) -> Result<()> {
+        let mut row = Row::new();
+        for &value in values {
+            row.add_cell((value, format));
+        }
+        self.append_row(row)
+    }
+
+    /// Same as `append_row`, but returns the 1-based index of the row just written, e.g. to
+    /// build a reference to it.
+    pub fn append_row_indexed(&mut self, row: Row) -> Result<usize> {
+        self.append_row(row)?;
+        Ok(self.sheet.max_row_index)
+    }
+
+    /// Writes the formula `base_formula` into column `col` (1-based) of `count` rows starting at
+    /// `start_row`, as a shared formula: only the first cell stores the formula text and the
+    /// others reference it, with relative references adjusted per row by Excel. For example
+    /// `A2*B2` starting at row 2 becomes `A3*B3` in row 3. This keeps large files much smaller
+    /// than repeating the formula in every cell.
+    ///
+    /// The rows hold only the formula cell. `start_row` must be after the last written row;
+    /// rows in between are left blank.
+    pub fn append_shared_formula_column(
+        &mut self,
+        col: usize,
+        start_row: usize,
+        count: usize,
+        base_formula: &str,
+    ) -> Result<()> {
         self.sheet
-            .write_row(self.writer, row.replace_strings(&mut self.shared_strings))
+            .write_shared_formula_rows(self.writer, col, start_row, count, base_formula)
     }
 
     pub fn append_blank_rows(&mut self, rows: usize) {
         self.sheet.write_blank_rows(rows)
     }
 
+    /// Unlike `append_blank_rows`, writes `count` rows of `cols` empty cells that all use the
+    /// cell format `format`, e.g. filled spacer rows between sections.
+    pub fn append_styled_blank_rows(&mut self, count: usize, cols: usize, format: u16) -> Result<()> {
+        for _ in 0..count {
+            let mut row = Row::new();
+            for _ in 0..cols {
+                row.add_cell(CellValue::StyledBlank(format));
+            }
+            self.append_row(row)?;
+        }
+        Ok(())
+    }
+
     /// Merges the range between `start` and `end` cells, specified as 1-based `(column, row)` pairs.
     /// For example, `(1, 2)` is equivalent to cell `A2`.
     pub fn merge_cells(&mut self, start: (usize, usize), end: (usize, usize)) -> Result<()> {
@@ -496,6 +1802,59 @@ impl<'a, 'b> SheetWriter<'a, 'b> {
         }
     }
 
+    /// Links the cell at the 1-based `(column, row)` pair to the external `url`. The cell's
+    /// content is written as usual; `display` is the link's display string and `tooltip` is
+    /// shown when hovering over the cell. All three are XML-escaped.
+    pub fn add_hyperlink(
+        &mut self,
+        cell: (usize, usize),
+        url: &str,
+        display: Option<&str>,
+        tooltip: Option<&str>,
+    ) -> Result<()> {
+        if cell.0 == 0 || cell.1 == 0 {
+            return Err(Error::new(ErrorKind::Other, "invalid cell"));
+        }
+        self.sheet.hyperlinks.push(Hyperlink {
+            cell: ref_id(cell.0, cell.1),
+            url: url.to_owned(),
+            display: display.map(str::to_owned),
+            tooltip: tooltip.map(str::to_owned),
+        });
+        Ok(())
+    }
+
+    /// Gives the numbers in `range` (e.g. `B2:D10`, or a single cell) the cell format `format`,
+    /// as returned by `Workbook::add_cust_number_format`, as if they had been written as
+    /// `(value, format)`. When ranges overlap, the one styled last wins. Returns an
+    /// `InvalidInput` error if `range` isn't a valid reference.
+    ///
+    /// As the sheet is streamed row by row, this only affects numeric cells appended after the
+    /// call, so call it before writing the rows. Rows already written are left as they are, as
+    /// are strings, booleans, formulas and blanks in the range, and numbers that already have a
+    /// format.
+    pub fn style_range(&mut self, range: &str, format: u16) -> Result<()> {
+        let mut refs = range.splitn(2, ':');
+        let start = refs.next().and_then(parse_ref);
+        let end = refs.next().map_or(start, parse_ref);
+        match (start, end) {
+            (Some(start), Some(end)) if start.0 <= end.0 && start.1 <= end.1 => {
+                self.sheet.range_styles.push(RangeStyle { start, end, format });
+                Ok(())
+            }
+            _ => Err(Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("invalid range {:?}", range),
+            )),
+        }
+    }
+
+    /// Turns off Excel's "number stored as text" warning for the cells of `range`, e.g. `A2:A100`
+    /// for a column of zip codes written as strings.
+    pub fn ignore_number_as_text(&mut self, range: &str) {
+        self.sheet.number_as_text_ranges.push(range.to_owned());
+    }
+
     /// Merges the range between `start_ref` and `end_ref` cells, specified as cell ref IDs (e.g.
     /// `B3`).
     pub fn merge_range(&mut self, start_ref: String, end_ref: String) -> Result<()> {
@@ -518,14 +1877,45 @@ impl<'a, 'b> SheetWriter<'a, 'b> {
     where
         F: FnOnce(&mut SheetWriter) -> Result<()> + Sized,
     {
-        self.sheet.write_head(self.writer)?;
+        self.begin()?;
+        write_data(self)?;
+        self.finish()
+    }
 
-        self.sheet.write_data_begin(self.writer)?;
+    // The children of <worksheet> must appear in the order defined by the schema
+    // (CT_Worksheet), otherwise strict readers reject the file:
+    //   sheetPr, dimension, sheetViews, sheetFormatPr, cols, sheetData, autoFilter,
+    //   mergeCells, conditionalFormatting, dataValidations, hyperlinks, printOptions,
+    //   pageMargins, pageSetup, headerFooter, rowBreaks, colBreaks, ignoredErrors,
+    //   drawing, tableParts, extLst
+    // New elements must be written from the matching position in `begin` or `finish`.
 
-        write_data(self)?;
+    /// Writes everything up to and including the opening `<sheetData>`.
+    pub(crate) fn begin(&mut self) -> Result<()> {
+        self.sheet.write_head(self.writer)?;
+        self.sheet.write_sheet_views(self.writer)?;
+        self.sheet.write_sheet_format(self.writer)?;
+        self.sheet.write_cols(self.writer)?;
 
+        self.sheet.write_data_begin(self.writer)
+    }
+
+    /// Writes everything from the closing `</sheetData>` on.
+    pub(crate) fn finish(&mut self) -> Result<()> {
         self.sheet.write_data_end(self.writer)?;
+        if let Some(header_row) = self.sheet.report_filter_row {
+            let (end_col, end_row) = (self.sheet.max_col_index, self.sheet.max_row_index);
+            self.sheet.add_auto_filter(1, end_col, header_row, end_row);
+        }
+        self.sheet.write_auto_filter(self.writer)?;
         self.sheet.write_merged_cells(self.writer)?;
+        self.sheet.write_conditional_formatting(self.writer)?;
+        self.sheet.write_hyperlinks(self.writer)?;
+        self.sheet.write_print_options(self.writer)?;
+        self.sheet.write_page_margins(self.writer)?;
+        self.sheet.write_page_breaks(self.writer)?;
+        self.sheet.write_ignored_errors(self.writer)?;
+        self.sheet.write_ext_list(self.writer)?;
         self.sheet.close(self.writer)
     }
 }
@@ -568,4 +1958,37 @@ mod chrono_tests {
             _ => panic!("invalid chrono::NaiveDate conversion to CellValue"),
         }
     }
+
+    #[test]
+    fn chrono_date_formatted() {
+        let date = NaiveDate::from_ymd(2012, 11, 10);
+        match (date, 164).to_cell_value() {
+            CellValue::NumberFormatted((n, 164)) if n == 41223. => {}
+            _ => panic!("invalid (chrono::NaiveDate, u16) conversion to CellValue"),
+        }
+
+        match (date.and_hms(15, 17, 39), 165).to_cell_value() {
+            CellValue::NumberFormatted((n, 165)) if n == 41223.63725694444 => {}
+            _ => panic!("invalid (chrono::NaiveDateTime, u16) conversion to CellValue"),
+        }
+    }
+
+    #[test]
+    fn chrono_datetime_with_time_zone() {
+        use chrono::{FixedOffset, TimeZone, Utc};
+
+        const EXPECTED: f64 = 41223.63725694444;
+        let utc = Utc.ymd(2012, 11, 10).and_hms(15, 17, 39);
+        match utc.to_cell_value() {
+            CellValue::Datetime(n) if n == EXPECTED => {}
+            _ => panic!("invalid chrono::DateTime<Utc> conversion to CellValue"),
+        }
+
+        // the same instant in UTC+2 is written as its local time, two hours later
+        let local = utc.with_timezone(&FixedOffset::east(2 * 3600));
+        match local.to_cell_value() {
+            CellValue::Datetime(n) if (n - EXPECTED - 2. / 24.).abs() < 1e-9 => {}
+            _ => panic!("invalid chrono::DateTime<FixedOffset> conversion to CellValue"),
+        }
+    }
 }
@@ -3,7 +3,7 @@ use std::io::*;
 use std::path::*;
 use std::collections::HashMap;
 
-use super::{escape_xml, Sheet, SheetWriter};
+use super::{escape_xml, CellValue, Row, Sheet, SheetWriter, WriteOptions};
 
 struct ArchiveFile {
     name: PathBuf,
@@ -26,6 +26,167 @@ fn path_format(path: &std::path::Path) -> String {
     })
 }
 
+/// Removes whitespace-only text between a closing `>` and the next `<`.
+/// Only meant for parts without significant whitespace in text nodes, such as styles.xml.
+fn strip_whitespace_between_tags(xml: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(xml.len());
+    let mut i = 0;
+    while i < xml.len() {
+        out.push(xml[i]);
+        if xml[i] == b'>' {
+            let mut j = i + 1;
+            while j < xml.len() && xml[j].is_ascii_whitespace() {
+                j += 1;
+            }
+            if j == xml.len() || xml[j] == b'<' {
+                i = j;
+                continue;
+            }
+        }
+        i += 1;
+    }
+    out
+}
+
+/// Basic syntax check of a number format code: quotes and brackets must be closed, escape
+/// characters (`\`, `_`, `*`) must be followed by a character, and there may be at most four
+/// `;`-separated sections.
+fn validate_number_format(pattern: &str) -> Result<()> {
+    let invalid = |msg: &str| Err(Error::new(ErrorKind::InvalidInput, format!("invalid number format {:?}: {}", pattern, msg)));
+    if pattern.is_empty() {
+        return invalid("empty format");
+    }
+    let mut sections = 1;
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if !chars.any(|c| c == '"') => return invalid("unmatched quote"),
+            '[' => {
+                let mut closed = false;
+                for c in chars.by_ref() {
+                    match c {
+                        ']' => {
+                            closed = true;
+                            break;
+                        }
+                        '[' => return invalid("nested '['"),
+                        _ => {}
+                    }
+                }
+                if !closed {
+                    return invalid("unmatched '['");
+                }
+            }
+            ']' => return invalid("unmatched ']'"),
+            '\\' | '_' | '*' if chars.next().is_none() => {
+                return invalid("escape character at the end")
+            }
+            ';' => sections += 1,
+            _ => {}
+        }
+    }
+    if sections > 4 {
+        return invalid("more than four sections");
+    }
+    Ok(())
+}
+
+const FORMAT_COLORS: [&str; 8] = ["black", "blue", "cyan", "green", "magenta", "red", "white", "yellow"];
+
+/// Checks one section of a number format code for `add_conditional_number_format`: it must be
+/// valid on its own, without `;`, and bracketed names must be colors (`[Red]`, `[Color12]`) or
+/// elapsed time (`[h]`). Conditions such as `[<0]` and locales such as `[$-409]` are allowed.
+fn validate_format_section(section: &str) -> Result<()> {
+    let invalid = |msg: String| Err(Error::new(ErrorKind::InvalidInput, format!("invalid number format section {:?}: {}", section, msg)));
+    validate_number_format(section)?;
+    let mut chars = section.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                chars.by_ref().any(|c| c == '"');
+            }
+            '\\' | '_' | '*' => {
+                chars.next();
+            }
+            ';' => return invalid("contains a section separator".to_string()),
+            '[' => {
+                let name: String = chars.by_ref().take_while(|&c| c != ']').collect();
+                if !name.starts_with(|c: char| c.is_ascii_alphabetic()) {
+                    continue;
+                }
+                let lower = name.to_ascii_lowercase();
+                let is_color = FORMAT_COLORS.contains(&lower.as_str())
+                    || lower
+                        .strip_prefix("color")
+                        .and_then(|n| n.parse::<u8>().ok())
+                        .map_or(false, |n| (1..=56).contains(&n));
+                let is_elapsed_time = lower.chars().all(|c| c == 'h' || c == 'm' || c == 's');
+                if !is_color && !is_elapsed_time {
+                    return invalid(format!("unknown color [{}]", name));
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Whether `value` is a plain decimal number that can be written as a number without losing
+/// anything: an optional `-`, digits with at most one `.`, and no leading zero other than
+/// before the `.`. So `42`, `-0.5` and `3.14` are numbers, while `007`, `+1`, `1e5`, `1,000`
+/// and ` 1` stay text. See `Workbook::add_data_sheet_with`.
+pub fn looks_like_number(value: &str) -> bool {
+    let digits = value.strip_prefix('-').unwrap_or(value);
+    let (int, frac) = match digits.find('.') {
+        Some(dot) => (&digits[..dot], Some(&digits[dot + 1..])),
+        None => (digits, None),
+    };
+    let all_digits = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+    all_digits(int) && (int == "0" || !int.starts_with('0')) && frac.map_or(true, all_digits)
+}
+
+const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
+type SheetXmlTransform = Box<dyn Fn(&mut Vec<u8>) + Send>;
+
+/// When Excel recalculates formulas, see `Workbook::set_calc_properties`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CalcMode {
+    /// Recalculate whenever a value changes (Excel's default).
+    Automatic,
+    /// Like `Automatic`, except for data tables.
+    AutomaticNoTable,
+    /// Only recalculate when the user asks for it (F9).
+    Manual,
+}
+
+impl CalcMode {
+    fn as_str(&self) -> &'static str {
+        match self {
+            CalcMode::Automatic => "auto",
+            CalcMode::AutomaticNoTable => "autoNoTable",
+            CalcMode::Manual => "manual",
+        }
+    }
+}
+
+/// A step of `Workbook::close_with_progress`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ProgressEvent {
+    /// The workbook-level parts (workbook, styles, shared strings, ...) are being generated.
+    CreatingParts,
+    /// Part `part` (1-based) of `total`, named `name` in the archive, is being compressed.
+    Zipping { part: usize, total: usize, name: String },
+    /// The archive is being written to the xlsx file. Not reported for in-memory workbooks.
+    Writing,
+}
+
+struct CalcProperties {
+    mode: CalcMode,
+    iterative: bool,
+    max_iterations: u32,
+}
+
 #[derive(Default)]
 pub struct Workbook {
     xlsx_file: Option<String>,
@@ -35,11 +196,27 @@ pub struct Workbook {
     sheets: Vec<SheetRef>,
     calc_chain: Vec<(String, usize)>,
     saved: bool,
-    cell_formats: CellFormats
+    cell_formats: CellFormats,
+    options: WriteOptions,
+    durable: bool,
+    sheet_xml_transforms: HashMap<usize, SheetXmlTransform>,
+    application_name: Option<String>,
+    open_sheets: HashMap<usize, Vec<u8>>,
+    /// Sheets added with `add_sheet` that haven't been written yet, by id.
+    added_sheets: HashMap<usize, Sheet>,
+    calc_properties: Option<CalcProperties>,
+    full_calc_on_load: bool,
+    min_compress_size: usize,
+    window: Option<(u32, u32, u16)>,
+    default_font_color: Option<u32>,
+    omit_theme: bool,
+    omit_calc_chain: bool,
+    bom: bool,
+    extensions: Vec<String>,
 }
 
-#[derive(Default)]
-struct CellFormats {
+#[derive(Default, Clone)]
+pub(crate) struct CellFormats {
     base_cust_id: u16,
     pub num_fmts: HashMap<u16,String>,
     pub cell_xfs: Vec<CellXf>
@@ -48,11 +225,12 @@ struct CellFormats {
 impl CellFormats {
     pub fn new() -> CellFormats {
         let base_cust_id = 165;
-        let mut fmts = vec![];
         // Default cell formats
-        fmts.push(CellXf {num_fmt_id: 0, font_id: 0, fill_id: 0, border_id: 0, xf_id: 0, apply_num_fmt: 1});
-        fmts.push(CellXf {num_fmt_id: 14, font_id: 0, fill_id: 0, border_id: 0, xf_id: 0, apply_num_fmt: 1});
-        fmts.push(CellXf {num_fmt_id: 22, font_id: 0, fill_id: 0, border_id: 0, xf_id: 0, apply_num_fmt: 1});
+        let fmts = vec![
+            CellXf {num_fmt_id: 0, font_id: 0, fill_id: 0, border_id: 0, xf_id: 0, apply_num_fmt: 1},
+            CellXf {num_fmt_id: 14, font_id: 0, fill_id: 0, border_id: 0, xf_id: 0, apply_num_fmt: 1},
+            CellXf {num_fmt_id: 22, font_id: 0, fill_id: 0, border_id: 0, xf_id: 0, apply_num_fmt: 1},
+        ];
         CellFormats {base_cust_id, num_fmts: HashMap::new(), cell_xfs: fmts}
     }
 
@@ -63,10 +241,57 @@ impl CellFormats {
         self.cell_xfs.push(CellXf{num_fmt_id: new_id, font_id: 0, fill_id: 0, border_id:  0, xf_id: 0, apply_num_fmt: 1});
         result
     }
+
+    /// Like `add_cust_number_format`, but returns the existing cell format if `pattern` was
+    /// already added.
+    pub fn find_or_add_number_format(&mut self, pattern: &str) -> u16 {
+        let existing = self
+            .num_fmts
+            .iter()
+            .find(|(_, p)| p.as_str() == pattern)
+            .and_then(|(id, _)| self.cell_xfs.iter().position(|xf| xf.num_fmt_id == *id));
+        match existing {
+            Some(idx) => idx as u16,
+            None => self.add_cust_number_format(pattern.to_string()),
+        }
+    }
+}
+
+/// Number formats registered once and shared by several workbooks, see
+/// `Workbook::create_with_styles`. The indexes returned here are valid in every workbook
+/// created from the registry, so formats don't have to be added again for each file.
+#[derive(Clone)]
+pub struct StyleRegistry {
+    cell_formats: CellFormats,
+}
+
+impl Default for StyleRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StyleRegistry {
+    pub fn new() -> StyleRegistry {
+        StyleRegistry {
+            cell_formats: CellFormats::new(),
+        }
+    }
+
+    /// Same as `Workbook::add_cust_number_format`.
+    pub fn add_cust_number_format(&mut self, format_str: String) -> u16 {
+        self.cell_formats.add_cust_number_format(format_str)
+    }
+
+    /// Same as `Workbook::try_add_number_format`.
+    pub fn try_add_number_format(&mut self, pattern: &str) -> Result<u16> {
+        validate_number_format(pattern)?;
+        Ok(self.add_cust_number_format(pattern.to_string()))
+    }
 }
 
 #[derive(Default, Clone)]
-struct CellXf {
+pub(crate) struct CellXf {
     pub num_fmt_id: u16,
     pub font_id: u16,
     pub fill_id: u16,
@@ -80,11 +305,14 @@ pub struct SharedStrings {
     count: usize,
     used: bool,
     strings: Vec<String>,
+    /// The escaped phonetic reading of each entry of `strings`, if any.
+    phonetics: Vec<Option<String>>,
 }
 
 struct SheetRef {
     id: usize,
     name: String,
+    print_title_rows: usize,
 }
 
 impl SharedStrings {
@@ -109,14 +337,54 @@ impl SharedStrings {
     pub fn add_count(&mut self) {
         self.count += 1;
     }
-    /// Takes a string value checks if it's present in shared strings and returns a CellValue with the index
+    /// Removes all strings, keeping the allocated capacity.
+    pub fn clear(&mut self) {
+        self.count = 0;
+        self.strings.clear();
+        self.phonetics.clear();
+    }
+    /// Takes a string value checks if it's present in shared strings and returns a CellValue with the index.
+    /// `val` must be escaped for XML. A table that isn't used returns the string itself
+    /// (unescaped) as a `CellValue::String`, so no cell refers to an index that isn't written.
     pub fn register(&mut self, val: &str) -> crate::CellValue {
+        if !self.used {
+            return crate::CellValue::String(crate::reader::unescape(val));
+        }
         self.add_count();
 
-        match self.strings.iter().position(|v| v == val) {
+        self.find_or_add(val, None)
+    }
+    /// Like `register`, but without counting a use: the cells referring to the returned value
+    /// are counted as they're written.
+    pub fn intern(&mut self, val: &str) -> crate::CellValue {
+        if !self.used {
+            return crate::CellValue::String(crate::reader::unescape(val));
+        }
+        self.find_or_add(val, None)
+    }
+    /// Like `register`, for a string with a phonetic reading (furigana). The same text with a
+    /// different reading, or without one, is a separate entry.
+    pub fn register_phonetic(&mut self, val: &str, ruby: &str) -> crate::CellValue {
+        if !self.used {
+            return crate::CellValue::Phonetic {
+                base: crate::reader::unescape(val),
+                ruby: crate::reader::unescape(ruby),
+            };
+        }
+        self.add_count();
+        self.find_or_add(val, Some(ruby))
+    }
+    fn find_or_add(&mut self, val: &str, ruby: Option<&str>) -> crate::CellValue {
+        let found = self
+            .strings
+            .iter()
+            .zip(self.phonetics.iter())
+            .position(|(v, p)| v == val && p.as_deref() == ruby);
+        match found {
             Some(idx) => crate::sheet::CellValue::SharedString(format!("{}", idx)),
             None => {
                 self.strings.push(val.to_owned());
+                self.phonetics.push(ruby.map(str::to_owned));
                 crate::sheet::CellValue::SharedString(format!("{}", (self.strings.len() - 1)))
             }
         }
@@ -124,54 +392,208 @@ impl SharedStrings {
 }
 
 impl Workbook {
-    /// Creates a workbook using shared strings
-    pub fn create(xlsx_file: &str) -> Self {
+    /// Same as `create`, with the number formats of `registry` already added, so that the
+    /// indexes it returned can be used in this workbook. Formats added to the workbook later
+    /// don't change the registry.
+    pub fn create_with_styles<P: Into<String>>(xlsx_file: P, registry: &StyleRegistry) -> Self {
+        let mut wb = Self::create(xlsx_file);
+        wb.cell_formats = registry.cell_formats.clone();
+        wb
+    }
+
+    fn new(xlsx_file: Option<String>, shared_strings: SharedStrings) -> Self {
         Self {
-            xlsx_file: Some(xlsx_file.to_owned()),
+            xlsx_file,
             archive_files: Vec::new(),
             max_sheet_index: 0,
-            shared_strings: SharedStrings::new(),
+            shared_strings,
             sheets: Vec::new(),
             calc_chain: Vec::new(),
             saved: false,
-            cell_formats: CellFormats::new()
+            cell_formats: CellFormats::new(),
+            options: WriteOptions::default(),
+            durable: false,
+            sheet_xml_transforms: HashMap::new(),
+            application_name: None,
+            open_sheets: HashMap::new(),
+            added_sheets: HashMap::new(),
+            calc_properties: None,
+            full_calc_on_load: false,
+            min_compress_size: 0,
+            window: None,
+            default_font_color: None,
+            omit_theme: false,
+            omit_calc_chain: false,
+            bom: false,
+            extensions: Vec::new(),
         }
     }
+
+    /// Creates a workbook using shared strings
+    pub fn create<P: Into<String>>(xlsx_file: P) -> Self {
+        Self::new(Some(xlsx_file.into()), SharedStrings::new())
+    }
     /// Creates a workbook not using shared strings
-    pub fn create_simple(xlsx_file: &str) -> Self {
-        Self {
-            xlsx_file: Some(xlsx_file.to_owned()),
-            archive_files: Vec::new(),
-            max_sheet_index: 0,
-            shared_strings: SharedStrings::new_unused(),
-            sheets: Vec::new(),
-            calc_chain: Vec::new(),
-            saved: false,
-            cell_formats: CellFormats::new()
-        }
+    pub fn create_simple<P: Into<String>>(xlsx_file: P) -> Self {
+        Self::new(Some(xlsx_file.into()), SharedStrings::new_unused())
     }
 
     pub fn create_in_memory() -> Self {
-        Self {
-            xlsx_file: None,
-            archive_files: Vec::new(),
-            max_sheet_index: 0,
-            shared_strings: SharedStrings::new_unused(),
-            sheets: Vec::new(),
-            calc_chain: Vec::new(),
-            saved: false,
-            cell_formats: CellFormats::new()
+        Self::new(None, SharedStrings::new_unused())
+    }
+
+    /// Adds `val` to the shared string table up front and returns a `CellValue` referring to it,
+    /// which can be reused in any number of cells without looking the string up again. Each
+    /// cell written with it counts as one use of the string. In workbooks without a shared
+    /// string table this is just a `CellValue::String`.
+    pub fn intern_string(&mut self, val: &str) -> CellValue {
+        if !self.shared_strings.used() {
+            return CellValue::String(val.to_owned());
         }
+        self.shared_strings.intern(&escape_xml(val))
+    }
+
+    /// Sets the application name recorded in the file's properties (docProps/app.xml).
+    /// Defaults to "SheetJS" for compatibility with files written by earlier versions.
+    pub fn set_application_name(&mut self, name: &str) {
+        self.application_name = Some(name.to_owned());
+    }
+
+    /// Sets how Excel recalculates the workbook's formulas, written as `<calcPr>` in
+    /// workbook.xml. With `iterative`, circular references are resolved by iterating at most
+    /// `max_iterations` times instead of being reported as errors. Without this call the
+    /// attributes are left out and Excel uses its defaults (automatic, not iterative).
+    pub fn set_calc_properties(&mut self, mode: CalcMode, iterative: bool, max_iterations: u32) {
+        self.calc_properties = Some(CalcProperties {
+            mode,
+            iterative,
+            max_iterations,
+        });
+    }
+
+    /// Makes Excel recalculate all formulas when opening the file (`fullCalcOnLoad` on
+    /// `<calcPr>`). Formula cells are written without cached values, so this is the simplest
+    /// way to have their results show up right away; other readers still see them as empty.
+    pub fn set_full_calc_on_load(&mut self, full_calc_on_load: bool) {
+        self.full_calc_on_load = full_calc_on_load;
+    }
+
+    /// Adds raw extension XML, one or more `<ext uri="...">` elements, to the `<extLst>` at
+    /// the end of the workbook part. The XML is written as is, so it must be well-formed.
+    pub fn add_ext(&mut self, xml: &str) {
+        self.extensions.push(xml.to_string());
+    }
+
+    /// Stores parts smaller than `size` bytes uncompressed in the archive, as deflating tiny
+    /// parts can make them larger. Larger parts are still deflated. Defaults to 0, which
+    /// deflates every part.
+    pub fn set_min_compress_size(&mut self, size: usize) {
+        self.min_compress_size = size;
+    }
+
+    /// Sets the size of the workbook window in twips (1/20 of a point) and the width of the
+    /// sheet tab bar relative to the horizontal scroll bar, in thousandths (600 is Excel's
+    /// default). The window is placed at the top left of the screen.
+    pub fn set_window(&mut self, width: u32, height: u32, tab_ratio: u16) {
+        self.window = Some((width, height, tab_ratio));
+    }
+
+    /// Sets the text color of the default font, used by every cell, as ARGB with alpha in the
+    /// high byte (e.g. `0xFF404040` for dark gray). Without this the text uses the theme's
+    /// dark text color, which is black.
+    pub fn set_default_font_color(&mut self, argb: u32) {
+        self.default_font_color = Some(argb);
+    }
+
+    /// Leaves out the theme part (xl/theme/theme1.xml). The default font then refers to no
+    /// theme: its color is written as black rgb (unless set with `set_default_font_color`)
+    /// and it no longer follows the theme's body font.
+    pub fn without_theme(&mut self) {
+        self.omit_theme = true;
+    }
+
+    /// Leaves out the calculation chain part (xl/calcChain.xml), even if sheets have formulas.
+    /// It only tells Excel in which order to calculate formulas, and Excel rebuilds it when
+    /// missing, while some readers and validators complain about it.
+    pub fn without_calc_chain(&mut self) {
+        self.omit_calc_chain = true;
+    }
+
+    /// Prefixes every part (all of them XML) with the UTF-8 byte order mark, for XML consumers
+    /// that require it. Off by default, as some consumers reject it; Excel reads both.
+    pub fn with_bom(&mut self, bom: bool) {
+        self.bom = bom;
+    }
+
+    /// When set, `close` also calls `sync_all` on the xlsx file, so it has reached the disk
+    /// by the time `close` returns. Off by default, as syncing can be slow.
+    pub fn set_durable(&mut self, durable: bool) {
+        self.durable = durable;
+    }
+
+    /// Writes minimal XML, without the newlines and indentation that are otherwise added
+    /// between elements of the worksheets and the style sheet. This saves a few bytes per row,
+    /// which adds up for large sheets. Must be called before writing any sheet.
+    pub fn compact(&mut self) {
+        self.options.compact = true;
+    }
+
+    /// Writes booleans as `TRUE`/`FALSE` string cells instead of boolean (`t="b"`) cells, for
+    /// readers that don't handle the latter. Excel then treats them as text, not as logical
+    /// values. Must be called before writing any sheet.
+    pub fn booleans_as_text(&mut self) {
+        self.options.booleans_as_text = true;
+    }
+
+    /// Uses the cell format `format`, as returned by `add_cust_number_format`, for all numbers
+    /// written without a format of their own, instead of Excel's General format, which shows
+    /// long numbers in scientific notation (e.g. an ID of 16 digits as `1.23457E+15`). Dates
+    /// and numbers written with a format keep theirs. Must be called before writing any sheet.
+    pub fn set_default_number_format(&mut self, format: u16) {
+        self.options.default_number_format = Some(format);
+    }
+
+    /// Writes number cells with an explicit `t="n"` type, for strict readers that treat cells
+    /// without a type as strings. Excel doesn't need it, as `n` is the default type. Must be
+    /// called before writing any sheet.
+    pub fn explicit_number_type(&mut self, explicit: bool) {
+        self.options.explicit_number_type = explicit;
+    }
+
+    /// Leaves out the `r` reference attribute of rows and cells that directly follow the
+    /// previous row or cell, which Excel infers from their position. This makes large sheets
+    /// noticeably smaller, but the XML harder to read, and some readers other than Excel
+    /// expect explicit references. Must be called before writing any sheet.
+    pub fn omit_cell_refs(&mut self) {
+        self.options.omit_refs = true;
+    }
+
+    /// Removes all sheets and their content so the workbook can be reused for another file,
+    /// keeping the allocated memory. Settings such as the output file, `compact` or the
+    /// calculation properties are kept, as are the number formats, so indexes returned by
+    /// `add_cust_number_format` stay valid. `Sheet`s created before the reset must not be used
+    /// afterwards: their ids would refer to sheets of the new content.
+    pub fn reset(&mut self) {
+        self.archive_files.clear();
+        self.max_sheet_index = 0;
+        self.shared_strings.clear();
+        self.sheets.clear();
+        self.calc_chain.clear();
+        self.saved = false;
+        self.sheet_xml_transforms.clear();
+        self.open_sheets.clear();
+        self.added_sheets.clear();
     }
 
     pub fn create_sheet(&mut self, sheet_name: &str) -> Sheet {
         self.max_sheet_index += 1;
 
-        let validated_name = crate::validate_name(sheet_name);
+        let validated_name = crate::valid_sheet_name(self.max_sheet_index, sheet_name);
 
         self.sheets.push(SheetRef {
             id: self.max_sheet_index,
             name: validated_name.clone(),
+            print_title_rows: 0,
         });
 
         // `Sheet` has a private field, so we can't just construct it here with needed values.
@@ -185,44 +607,256 @@ impl Workbook {
     }
 
     pub fn close(&mut self) -> Result<Option<Vec<u8>>> {
-        self.create_files()?;
+        self.close_with_progress(|_| {})
+    }
+
+    /// Same as `close`, calling `progress` as the work goes on, e.g. to drive a progress bar
+    /// for large workbooks. Sheets are serialized when they're written, so closing is mostly
+    /// compressing the parts: one `ProgressEvent::Zipping` is reported per part.
+    pub fn close_with_progress<F>(&mut self, mut progress: F) -> Result<Option<Vec<u8>>>
+    where
+        F: FnMut(ProgressEvent),
+    {
+        let buf = self.build_archive(&mut progress)?;
+
+        if let Some(xlsx_file) = &self.xlsx_file {
+            progress(ProgressEvent::Writing);
+            let mut file = File::create(xlsx_file)?;
+            file.write_all(&buf)?;
+            file.flush()?;
+            if self.durable {
+                file.sync_all()?;
+            }
+            self.saved = true;
+
+            Ok(None)
+        } else {
+            Ok(Some(buf))
+        }
+    }
+
+    /// Like `add_cust_number_format`, but first checks the format code for obvious syntax
+    /// errors (unbalanced quotes or brackets, too many sections) that would make Excel show
+    /// the raw value, and returns an `InvalidInput` error for them.
+    pub fn try_add_number_format(&mut self, pattern: &str) -> Result<u16> {
+        validate_number_format(pattern)?;
+        Ok(self.add_cust_number_format(pattern.to_string()))
+    }
+
+    /// Adds a number format with separate sections for positive numbers, negative numbers,
+    /// zero and text, e.g. `("#,##0.00", "[Red]-#,##0.00", "0.00", "@")` for negatives in red,
+    /// and returns its index like `add_cust_number_format`. As in Excel, an empty section hides
+    /// the values it applies to. Each section is checked like `try_add_number_format`, and
+    /// bracketed color names must be one of Excel's eight colors or `[Color1]` to `[Color56]`;
+    /// otherwise an `InvalidInput` error is returned.
+    pub fn add_conditional_number_format(&mut self, positive: &str, negative: &str, zero: &str, text: &str) -> Result<u16> {
+        for section in [positive, negative, zero, text].iter().filter(|s| !s.is_empty()) {
+            validate_format_section(section)?;
+        }
+        Ok(self.add_cust_number_format(format!("{};{};{};{}", positive, negative, zero, text)))
+    }
+
+    /// Adds a number format with thousands grouping and `decimals` decimal places, e.g.
+    /// `#,##0.00` for 2, and returns its index like `add_cust_number_format`.
+    ///
+    /// Format codes are always stored with `,` as the grouping and `.` as the decimal token,
+    /// and cell values always use `.`. Excel replaces both tokens with the viewer's regional
+    /// separators when displaying, so in a German locale this shows `1.234,56`. This is why the
+    /// separators aren't configurable here: a code containing other characters would show them
+    /// literally instead of grouping digits.
+    pub fn add_grouped_number_format(&mut self, decimals: u8) -> u16 {
+        let mut pattern = "#,##0".to_string();
+        if decimals > 0 {
+            pattern.push('.');
+            pattern.push_str(&"0".repeat(decimals as usize));
+        }
+        self.add_cust_number_format(pattern)
+    }
+
+    /// Adds the number format `0.00"%"`, for values that are already percentages (`25.0` for
+    /// 25%): it shows them with a literal `%` sign, e.g. `25.00%`. Returns its index like
+    /// `add_cust_number_format`.
+    ///
+    /// This differs from Excel's percent formats such as `0.00%`, which multiply the value by
+    /// 100 and are meant for fractions (`0.25` for 25%); using them for values like `25.0`
+    /// shows `2500.00%`.
+    pub fn add_percent_display_format(&mut self) -> u16 {
+        self.add_cust_number_format("0.00\"%\"".to_string())
+    }
+
+    /// Adds a number format that shows numbers as a whole part and a fraction with up to
+    /// `denominator_digits` digits in the denominator, e.g. `# ?/?` for 1 (`3 1/2`) or
+    /// `# ??/??` for 2 (`3 25/64`), and returns its index like `add_cust_number_format`.
+    /// Excel supports 1 to 3 digits; other values return an `InvalidInput` error.
+    pub fn add_fraction_format(&mut self, denominator_digits: u8) -> Result<u16> {
+        if !(1..=3).contains(&denominator_digits) {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("fraction denominators have 1 to 3 digits, not {}", denominator_digits),
+            ));
+        }
+        let digits = "?".repeat(denominator_digits as usize);
+        Ok(self.add_cust_number_format(format!("# {}/{}", digits, digits)))
+    }
+
+    /// Builds the xlsx archive from the sheets written so far and returns its bytes.
+    /// Unlike `close`, nothing is written to disk and the workbook isn't marked as saved, so
+    /// more sheets can still be written and `close` called afterwards.
+    pub fn to_bytes(&mut self) -> Result<Vec<u8>> {
+        self.build_archive(&mut |_| {})
+    }
+
+    fn build_archive(&mut self, progress: &mut dyn FnMut(ProgressEvent)) -> Result<Vec<u8>> {
+        progress(ProgressEvent::CreatingParts);
+        let files = self.create_files()?;
 
         let mut buf = Vec::new();
         {
             let mut cursor = Cursor::new(&mut buf);
             let mut writer = zip::ZipWriter::new(&mut cursor);
-            for archive_file in self.archive_files.iter() {
-                let options = zip::write::FileOptions::default();
+            let total = self.archive_files.len() + files.len();
+            for (index, archive_file) in self.archive_files.iter().chain(files.iter()).enumerate() {
+                progress(ProgressEvent::Zipping {
+                    part: index + 1,
+                    total,
+                    name: path_format(&archive_file.name),
+                });
+                let method = if archive_file.data.len() < self.min_compress_size {
+                    zip::CompressionMethod::Stored
+                } else {
+                    zip::CompressionMethod::Deflated
+                };
+                let options = zip::write::FileOptions::default().compression_method(method);
                 writer.start_file(path_format(&archive_file.name), options)?;
+                if self.bom {
+                    writer.write_all(UTF8_BOM)?;
+                }
                 writer.write_all(&archive_file.data)?;
             }
 
             writer.finish()?;
         }
 
-        if let Some(xlsx_file) = &self.xlsx_file {
-            let mut file = File::create(xlsx_file)?;
-            file.write_all(&buf)?;
-            self.saved = true;
+        Ok(buf)
+    }
 
-            Ok(None)
-        } else {
-            Ok(Some(buf))
+    /// Consumes the workbook and returns the bytes of the xlsx archive, without doing any IO.
+    /// Building and compressing the archive is CPU-bound, so in an async service it can be
+    /// run on a blocking thread (e.g. tokio's `spawn_blocking(move || wb.into_bytes())`) and
+    /// the bytes then written with async IO, instead of calling `close` on the runtime.
+    /// Nothing is written to the workbook's file, not even when it's dropped.
+    pub fn into_bytes(mut self) -> Result<Vec<u8>> {
+        self.saved = true;
+        self.to_bytes()
+    }
+
+    /// Writes the parts of the workbook as files under `dir` instead of zipping them, e.g.
+    /// `dir/xl/worksheets/sheet1.xml`, to inspect or diff them. Missing directories are created
+    /// and existing files overwritten.
+    pub fn write_unpacked(&mut self, dir: &str) -> Result<()> {
+        let files = self.create_files()?;
+        for archive_file in self.archive_files.iter().chain(files.iter()) {
+            let path = Path::new(dir).join(&archive_file.name);
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            let mut file = File::create(path)?;
+            if self.bom {
+                file.write_all(UTF8_BOM)?;
+            }
+            file.write_all(&archive_file.data)?;
         }
+        Ok(())
+    }
+
+    /// Zips the files under `in_dir` into the xlsx file `out_file`, the reverse of
+    /// `write_unpacked`, e.g. to repack parts edited by hand. Parts are deflated like in
+    /// `close`; Excel accepts both deflated and stored parts.
+    pub fn pack_dir(in_dir: &str, out_file: &str) -> Result<()> {
+        fn collect(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+            for entry in std::fs::read_dir(dir)? {
+                let path = entry?.path();
+                if path.is_dir() {
+                    collect(&path, files)?;
+                } else {
+                    files.push(path);
+                }
+            }
+            Ok(())
+        }
+
+        let root = Path::new(in_dir);
+        let mut files = Vec::new();
+        collect(root, &mut files)?;
+        let mut names: Vec<(String, PathBuf)> = files
+            .into_iter()
+            .filter_map(|path| {
+                let name = path_format(path.strip_prefix(root).ok()?);
+                Some((name, path))
+            })
+            .collect();
+        // [Content_Types].xml goes first, as in files written by Excel
+        names.sort_by_key(|(name, _)| (name != "[Content_Types].xml", name.clone()));
+
+        let mut writer = zip::ZipWriter::new(File::create(out_file)?);
+        for (name, path) in names {
+            writer.start_file(name, zip::write::FileOptions::default())?;
+            writer.write_all(&std::fs::read(path)?)?;
+        }
+        writer.finish()?;
+        Ok(())
     }
 
     pub fn add_cust_number_format(&mut self, format_str: String) -> u16 {
         self.cell_formats.add_cust_number_format(format_str)
     }
 
-    fn create_files(&mut self) -> Result<()> {
+    /// Generates the workbook-level parts. They're returned rather than stored so that the
+    /// archive can be built more than once without duplicating them.
+    fn create_files(&mut self) -> Result<Vec<ArchiveFile>> {
+        if !self.open_sheets.is_empty() {
+            return Err(Error::new(ErrorKind::Other, "a sheet opened with open_sheet hasn't been finished"));
+        }
+        if let Some(sheet) = self.added_sheets.values().next() {
+            return Err(Error::new(ErrorKind::Other, format!("sheet {} was added but never written", sheet.name)));
+        }
+        if !self.sheets.is_empty() {
+            return self.create_workbook_files();
+        }
+
+        // Excel refuses to open a workbook without any sheet, so a blank one is added to these
+        // parts only: the workbook is left as it is and sheets can still be added to it.
+        let mut sheet = Sheet::default();
+        sheet.id = self.max_sheet_index + 1;
+        sheet.name = "Sheet1".to_owned();
+        sheet.options = self.options;
+        let mut writer = Vec::new();
+        SheetWriter::new(&mut sheet, &mut writer, &mut self.shared_strings).write(|_| Ok(()))?;
+
+        self.sheets.push(SheetRef {
+            id: sheet.id,
+            name: sheet.name.clone(),
+            print_title_rows: 0,
+        });
+        let files = self.create_workbook_files();
+        self.sheets.pop();
+        let mut files = files?;
+        files.push(ArchiveFile {
+            name: Self::sheet_path(sheet.id),
+            data: writer,
+        });
+        Ok(files)
+    }
+
+    fn create_workbook_files(&mut self) -> Result<Vec<ArchiveFile>> {
+        let mut files = Vec::new();
         let mut root = PathBuf::new();
 
         // [Content_Types].xml
         root.push("[Content_Types].xml");
         let mut writer = Vec::new();
         self.create_content_types(&mut writer)?;
-        self.archive_files.push(ArchiveFile {
+        files.push(ArchiveFile {
             name: root.clone(),
             data: writer,
         });
@@ -233,7 +867,7 @@ impl Workbook {
         root.push(".rels");
         let mut writer = Vec::new();
         Self::create_rels(&mut writer)?;
-        self.archive_files.push(ArchiveFile {
+        files.push(ArchiveFile {
             name: root.clone(),
             data: writer,
         });
@@ -245,7 +879,7 @@ impl Workbook {
         root.push("app.xml");
         let mut writer = Vec::new();
         self.create_app(&mut writer)?;
-        self.archive_files.push(ArchiveFile {
+        files.push(ArchiveFile {
             name: root.clone(),
             data: writer,
         });
@@ -253,7 +887,7 @@ impl Workbook {
         root.push("core.xml");
         let mut writer = Vec::new();
         Self::create_core(&mut writer)?;
-        self.archive_files.push(ArchiveFile {
+        files.push(ArchiveFile {
             name: root.clone(),
             data: writer,
         });
@@ -265,45 +899,50 @@ impl Workbook {
         root.push("styles.xml");
         let mut writer = Vec::new();
         self.create_styles(&mut writer)?;
-        self.archive_files.push(ArchiveFile {
-            name: root.clone(),
-            data: writer,
-        });
-        root.pop();
-        //if self.shared_strings.used(){
-        root.push("sharedStrings.xml");
-        let mut writer = Vec::new();
-        self.create_shared_strings(&mut writer)?;
-        self.archive_files.push(ArchiveFile {
+        if self.options.compact {
+            writer = strip_whitespace_between_tags(&writer);
+        }
+        files.push(ArchiveFile {
             name: root.clone(),
             data: writer,
         });
         root.pop();
-        //}
+        if self.shared_strings.used() {
+            root.push("sharedStrings.xml");
+            let mut writer = Vec::new();
+            self.create_shared_strings(&mut writer)?;
+            files.push(ArchiveFile {
+                name: root.clone(),
+                data: writer,
+            });
+            root.pop();
+        }
         root.push("workbook.xml");
         let mut writer = Vec::new();
         self.create_workbook(&mut writer)?;
-        self.archive_files.push(ArchiveFile {
-            name: root.clone(),
-            data: writer,
-        });
-        root.pop();
-        
-        root.push("calcChain.xml");
-        let mut writer = Vec::new();
-        self.create_calc_chain(&mut writer)?;
-        self.archive_files.push(ArchiveFile {
+        files.push(ArchiveFile {
             name: root.clone(),
             data: writer,
         });
         root.pop();
 
+        if !self.omit_calc_chain {
+            root.push("calcChain.xml");
+            let mut writer = Vec::new();
+            self.create_calc_chain(&mut writer)?;
+            files.push(ArchiveFile {
+                name: root.clone(),
+                data: writer,
+            });
+            root.pop();
+        }
+
         // xl/_rels
         root.push("_rels");
         root.push("workbook.xml.rels");
         let mut writer = Vec::new();
         self.create_xl_rels(&mut writer)?;
-        self.archive_files.push(ArchiveFile {
+        files.push(ArchiveFile {
             name: root.clone(),
             data: writer,
         });
@@ -311,16 +950,18 @@ impl Workbook {
         root.pop();
 
         // xl/theme
-        root.push("theme");
-        root.push("theme1.xml");
-        let mut writer = Vec::new();
-        Self::create_xl_theme(&mut writer)?;
-        self.archive_files.push(ArchiveFile {
-            name: root.clone(),
-            data: writer,
-        });
-        root.pop();
-        root.pop();
+        if !self.omit_theme {
+            root.push("theme");
+            root.push("theme1.xml");
+            let mut writer = Vec::new();
+            Self::create_xl_theme(&mut writer)?;
+            files.push(ArchiveFile {
+                name: root.clone(),
+                data: writer,
+            });
+            root.pop();
+            root.pop();
+        }
 
         /*
                 // xl/worksheets
@@ -328,33 +969,182 @@ impl Workbook {
                 root.push("sheet1.xml");
                 let mut writer = Vec::new();
                 Self::create_sample_sheet(&mut writer)?;
-                self.archive_files.push(ArchiveFile {name: root.clone(), data: writer});
+                files.push(ArchiveFile {name: root.clone(), data: writer});
                 root.pop();
                 root.pop();
         */
-        Ok(())
+        Ok(files)
+    }
+
+    /// Registers a function that gets the serialized XML of the sheet with id `sheet_id`
+    /// after `write_sheet` and may modify it before it's added to the archive, e.g. to add
+    /// elements this crate doesn't support. Must be registered before the sheet is written.
+    ///
+    /// The function works on raw bytes: keeping the XML well-formed and its elements in
+    /// schema order is up to the caller. The function must be `Send` so that the workbook can
+    /// be moved to another thread, see `into_bytes`.
+    pub fn with_sheet_xml_transform<F>(&mut self, sheet_id: usize, f: F)
+    where
+        F: Fn(&mut Vec<u8>) + Send + 'static,
+    {
+        self.sheet_xml_transforms.insert(sheet_id, Box::new(f));
     }
 
     pub fn write_sheet<F>(&mut self, sheet: &mut Sheet, write_data: F) -> Result<()>
     where
         F: FnOnce(&mut SheetWriter) -> Result<()> + Sized,
     {
-        let mut root = PathBuf::new();
-        root.push("xl");
-        root.push("worksheets");
-        root.push(format!("sheet{}.xml", sheet.id));
         for cc in &sheet.calc_chain {
             self.calc_chain.push((cc.to_owned(), sheet.id));
         }
 
+        sheet.options = self.options;
         let mut writer = Vec::new();
-        let sw = &mut SheetWriter::new(sheet, &mut writer, &mut self.shared_strings);
+        let sw = &mut SheetWriter::new(sheet, &mut writer, &mut self.shared_strings)
+            .with_cell_formats(&mut self.cell_formats);
         sw.write(write_data)?;
+        self.add_sheet_part(sheet, writer);
+        Ok(())
+    }
+
+    /// Like `create_sheet`, but the sheet is kept in the workbook, which returns its id. Set it
+    /// up through `sheet_mut` and write it with `write_sheet_by_id`. Unlike a detached `Sheet`,
+    /// it can't be written twice, and closing the workbook fails if it was never written.
+    pub fn add_sheet(&mut self, sheet_name: &str) -> usize {
+        let sheet = self.create_sheet(sheet_name);
+        let id = sheet.id;
+        self.added_sheets.insert(id, sheet);
+        id
+    }
+
+    /// The sheet with id `id` added with `add_sheet`, if it hasn't been written yet.
+    pub fn sheet_mut(&mut self, id: usize) -> Option<&mut Sheet> {
+        self.added_sheets.get_mut(&id)
+    }
+
+    /// Same as `write_sheet`, for a sheet added with `add_sheet`. Returns a `NotFound` error if
+    /// there's no such sheet or it has already been written.
+    pub fn write_sheet_by_id<F>(&mut self, id: usize, write_data: F) -> Result<()>
+    where
+        F: FnOnce(&mut SheetWriter) -> Result<()> + Sized,
+    {
+        let mut sheet = self.added_sheets.remove(&id).ok_or_else(|| {
+            Error::new(ErrorKind::NotFound, format!("no unwritten sheet with id {}", id))
+        })?;
+        self.write_sheet(&mut sheet, write_data)
+    }
+
+    /// Creates a sheet named `name` and writes `data` to it, one row per slice. Values are
+    /// written as text, including ones starting with `=`; empty strings leave the cell empty.
+    /// Rows may have different lengths, and empty `data` gives an empty sheet.
+    pub fn add_data_sheet(&mut self, name: &str, data: &[&[&str]]) -> Result<()> {
+        self.add_data_sheet_with(name, data, |_| false)
+    }
+
+    /// Same as `add_data_sheet`, but values for which `is_number` returns true are written as
+    /// numbers, if they parse as one. `looks_like_number` is a conservative choice that keeps
+    /// values with leading zeros, such as phone numbers, as text; pass your own predicate to
+    /// decide differently.
+    pub fn add_data_sheet_with(&mut self, name: &str, data: &[&[&str]], is_number: fn(&str) -> bool) -> Result<()> {
+        let mut sheet = self.create_sheet(name);
+        self.write_sheet(&mut sheet, |sw| {
+            for values in data {
+                let mut row = Row::new();
+                for value in values.iter() {
+                    if value.is_empty() {
+                        row.add_empty_cells(1);
+                        continue;
+                    }
+                    match value.parse::<f64>() {
+                        Ok(n) if n.is_finite() && is_number(value) => row.add_cell(n),
+                        _ => row.add_cell(CellValue::String(value.to_string())),
+                    }
+                }
+                sw.append_row(row)?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Starts writing `sheet` incrementally, or continues where the last call left off if the
+    /// sheet is already open. Rows appended through the returned writer are kept in the
+    /// workbook, so the sheet can be filled across several calls, e.g. a header from one source
+    /// and the body from another, before being completed with `finish_sheet`.
+    pub fn open_sheet<'a>(&'a mut self, sheet: &'a mut Sheet) -> Result<SheetWriter<'a, 'a>> {
+        let is_new = !self.open_sheets.contains_key(&sheet.id);
+        if is_new {
+            for cc in &sheet.calc_chain {
+                self.calc_chain.push((cc.to_owned(), sheet.id));
+            }
+            sheet.options = self.options;
+        }
+        let writer = self.open_sheets.entry(sheet.id).or_default();
+        let mut sw = SheetWriter::new(sheet, writer, &mut self.shared_strings)
+            .with_cell_formats(&mut self.cell_formats);
+        if is_new {
+            sw.begin()?;
+        }
+        Ok(sw)
+    }
+
+    /// Completes a sheet opened with `open_sheet` and adds it to the workbook.
+    pub fn finish_sheet(&mut self, sheet: &mut Sheet) -> Result<()> {
+        let mut writer = match self.open_sheets.remove(&sheet.id) {
+            Some(writer) => writer,
+            None => return Err(Error::new(ErrorKind::Other, format!("sheet {} is not open", sheet.name))),
+        };
+        SheetWriter::new(sheet, &mut writer, &mut self.shared_strings).finish()?;
+        self.add_sheet_part(sheet, writer);
+        Ok(())
+    }
+
+    fn add_sheet_part(&mut self, sheet: &Sheet, mut writer: Vec<u8>) {
+        if let Some(sheet_ref) = self.sheets.iter_mut().find(|s| s.id == sheet.id) {
+            sheet_ref.print_title_rows = sheet.print_title_rows();
+        }
+        if let Some(transform) = self.sheet_xml_transforms.get(&sheet.id) {
+            transform(&mut writer);
+        }
         self.archive_files.push(ArchiveFile {
-            name: root,
+            name: Self::sheet_path(sheet.id),
             data: writer,
         });
-        Ok(())
+        if let Some(rels) = sheet.rels_xml() {
+            let mut name = PathBuf::new();
+            name.push("xl");
+            name.push("worksheets");
+            name.push("_rels");
+            name.push(format!("sheet{}.xml.rels", sheet.id));
+            self.archive_files.push(ArchiveFile { name, data: rels });
+        }
+    }
+
+    /// Reads back the cell values of the sheet named `sheet_name` from a written xlsx file, as
+    /// rows of text: shared and inline strings are resolved, booleans become `TRUE`/`FALSE` and
+    /// numbers are given as stored. Missing cells are empty strings; each row ends at its last
+    /// cell. This is a minimal reader meant for verifying output, not a general xlsx parser.
+    pub fn read_sheet_values(bytes: &[u8], sheet_name: &str) -> Result<Vec<Vec<String>>> {
+        crate::reader::read_sheet_values(bytes, sheet_name)
+    }
+
+    /// Returns the raw worksheet XML of an already written sheet, looked up by the name it was
+    /// created with. Returns `None` if there is no such sheet or it hasn't been written yet.
+    pub fn sheet_xml(&self, name: &str) -> Option<String> {
+        let name = crate::validate_name(name);
+        let sheet = self.sheets.iter().find(|sf| sf.name == name)?;
+        let path = Self::sheet_path(sheet.id);
+        self.archive_files
+            .iter()
+            .find(|f| f.name == path)
+            .map(|f| String::from_utf8_lossy(&f.data).into_owned())
+    }
+
+    fn sheet_path(id: usize) -> PathBuf {
+        let mut root = PathBuf::new();
+        root.push("xl");
+        root.push("worksheets");
+        root.push(format!("sheet{}.xml", id));
+        root
     }
 
     fn create_calc_chain(&mut self, writer: &mut dyn Write) -> Result<()> {
@@ -392,11 +1182,19 @@ impl Workbook {
             writer.write_all(wb.as_bytes())?;
         }
 
+        if self.shared_strings.used() {
+            writer.write_all(br#"
+    <Override PartName="/xl/sharedStrings.xml" ContentType="application/vnd.openxmlformats-officedocument.spreadsheetml.sharedStrings+xml"/>"#)?;
+        }
+
+        if !self.omit_theme {
+            writer.write_all(br#"
+    <Override PartName="/xl/theme/theme1.xml" ContentType="application/vnd.openxmlformats-officedocument.theme+xml"/>"#)?;
+        }
+
         let tail = br#"
-    <Override PartName="/xl/theme/theme1.xml" ContentType="application/vnd.openxmlformats-officedocument.theme+xml"/>
     <Override PartName="/xl/styles.xml"
               ContentType="application/vnd.openxmlformats-officedocument.spreadsheetml.styles+xml"/>
-    <Override PartName="/xl/sharedStrings.xml" ContentType="application/vnd.openxmlformats-officedocument.spreadsheetml.sharedStrings+xml"/>
     <Override PartName="/docProps/core.xml" ContentType="application/vnd.openxmlformats-package.core-properties+xml"/>
     <Override PartName="/docProps/app.xml"
               ContentType="application/vnd.openxmlformats-officedocument.extended-properties+xml"/>
@@ -418,38 +1216,34 @@ impl Workbook {
     }
 
     fn create_app(&mut self, writer: &mut dyn Write) -> Result<()> {
-        let xml = br#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+        let head = br#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
 <Properties xmlns="http://schemas.openxmlformats.org/officeDocument/2006/extended-properties"
             xmlns:vt="http://schemas.openxmlformats.org/officeDocument/2006/docPropsVTypes">
-    <Application>SheetJS</Application>
+    "#;
+        writer.write_all(head)?;
+        write!(writer, "<Application>{}</Application>", escape_xml(self.application_name.as_deref().unwrap_or("SheetJS")))?;
+        write!(
+            writer,
+            r#"
     <HeadingPairs>
         <vt:vector size="2" baseType="variant">
             <vt:variant>
                 <vt:lpstr>Worksheets</vt:lpstr>
             </vt:variant>
             <vt:variant>
-                <vt:i4>1</vt:i4>
+                <vt:i4>{}</vt:i4>
             </vt:variant>
         </vt:vector>
     </HeadingPairs>
     <TitlesOfParts>
-    <vt:vector size="1" baseType="lpstr">
-    <vt:lpstr>SheetJS</vt:lpstr>
-    "#;
-        writer.write_all(xml)?;
-
-        /* let vector = format!(
-            "<vt:vector size=\"{}\" baseType=\"lpstr\">",
+    <vt:vector size="{}" baseType="lpstr">
+    "#,
+            self.sheets.len(),
             self.sheets.len()
-        );
-        writer.write_all(vector.as_bytes())?;
+        )?;
         for sf in self.sheets.iter() {
-            let str = format!(
-                "<vt:lpstr>{}</vt:lpstr>",
-                sf.name
-            );
-            writer.write_all(str.as_bytes())?;
-        } */
+            write!(writer, "<vt:lpstr>{}</vt:lpstr>", sf.name)?;
+        }
         let tail = r#"</vt:vector>
     </TitlesOfParts>
 </Properties>
@@ -473,7 +1267,7 @@ impl Workbook {
             xmlns:vt="http://schemas.openxmlformats.org/officeDocument/2006/docPropsVTypes">
 "#;
         writer.write_all(xml)?;
-        if self.cell_formats.num_fmts.len() > 0 {
+        if !self.cell_formats.num_fmts.is_empty() {
             let num_fmts = format!("    <numFmts count=\"{}\">", self.cell_formats.num_fmts.len());
             writer.write_all(num_fmts.as_bytes())?;
             // Sort the map for consistent XML format
@@ -486,14 +1280,21 @@ impl Workbook {
             let fmt_tail = "\n    </numFmts>\n".as_bytes();
             writer.write_all(fmt_tail)?;
         }
-        let mid = br#"    <fonts count="1">
-        <font>
-            <sz val="12"/>
-            <color theme="1"/>
-            <name val="Calibri"/>
-            <family val="2"/>
-            <scheme val="minor"/>
-        </font>
+        let font_color = match (self.default_font_color, !self.omit_theme) {
+            (Some(argb), _) => format!("<color rgb=\"{:08X}\"/>", argb),
+            (None, true) => "<color theme=\"1\"/>".to_string(),
+            (None, false) => "<color rgb=\"FF000000\"/>".to_string(),
+        };
+        let fonts = format!(
+            "    <fonts count=\"1\">\n        <font>\n            <sz val=\"12\"/>\n            {}\n",
+            font_color
+        );
+        writer.write_all(fonts.as_bytes())?;
+        writer.write_all(b"            <name val=\"Calibri\"/>\n            <family val=\"2\"/>\n")?;
+        if !self.omit_theme {
+            writer.write_all(b"            <scheme val=\"minor\"/>\n")?;
+        }
+        let mid = br#"        </font>
     </fonts>
     <fills count="2">
         <fill>
@@ -516,7 +1317,7 @@ impl Workbook {
         <xf numFmtId="0" fontId="0" fillId="0" borderId="0"/>
     </cellStyleXfs>"#;
         writer.write_all(mid)?;
-        if self.cell_formats.cell_xfs.len() > 0 {
+        if !self.cell_formats.cell_xfs.is_empty() {
             let cell_xfs_start = format!("\n    <cellXfs count=\"{}\">", self.cell_formats.cell_xfs.len());
             writer.write_all(cell_xfs_start.as_bytes())?;
             for xf in &self.cell_formats.cell_xfs {
@@ -539,12 +1340,19 @@ impl Workbook {
         let xml = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
 <workbook xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main" xmlns:r="http://schemas.openxmlformats.org/officeDocument/2006/relationships">
     <workbookPr date1904="false"/>
-    <sheets>"#;
+    "#;
         let tail = r#"
-    </sheets>
 </workbook>
         "#;
         writer.write_all(xml.as_bytes())?;
+        if let Some((width, height, tab_ratio)) = self.window {
+            let book_views = format!(
+                "<bookViews><workbookView xWindow=\"0\" yWindow=\"0\" windowWidth=\"{}\" windowHeight=\"{}\" tabRatio=\"{}\"/></bookViews>",
+                width, height, tab_ratio
+            );
+            writer.write_all(book_views.as_bytes())?;
+        }
+        writer.write_all(b"<sheets>")?;
         for sf in self.sheets.iter() {
             let str = format!(
                 "<sheet name=\"{}\" sheetId=\"{}\" r:id=\"rId{}\"/>",
@@ -554,6 +1362,36 @@ impl Workbook {
             );
             writer.write_all(str.as_bytes())?;
         }
+        writer.write_all(b"\n    </sheets>")?;
+        if self.sheets.iter().any(|sf| sf.print_title_rows > 0) {
+            writer.write_all(b"\n    <definedNames>")?;
+            for (index, sf) in self.sheets.iter().enumerate().filter(|(_, sf)| sf.print_title_rows > 0) {
+                let name = sf.name.replace("&apos;", "&apos;&apos;");
+                let defined_name = format!(
+                    "<definedName name=\"_xlnm.Print_Titles\" localSheetId=\"{}\">&apos;{}&apos;!$1:${}</definedName>",
+                    index, name, sf.print_title_rows
+                );
+                writer.write_all(defined_name.as_bytes())?;
+            }
+            writer.write_all(b"</definedNames>")?;
+        }
+        if self.calc_properties.is_some() || self.full_calc_on_load {
+            let mut s = String::from("\n    <calcPr");
+            if let Some(calc) = &self.calc_properties {
+                s.push_str(&format!(" calcMode=\"{}\"", calc.mode.as_str()));
+                if calc.iterative {
+                    s.push_str(&format!(" iterate=\"1\" iterateCount=\"{}\"", calc.max_iterations));
+                }
+            }
+            if self.full_calc_on_load {
+                s.push_str(" fullCalcOnLoad=\"1\"");
+            }
+            s.push_str("/>");
+            writer.write_all(s.as_bytes())?;
+        }
+        if !self.extensions.is_empty() {
+            write!(writer, "\n    <extLst>{}</extLst>", self.extensions.concat())?;
+        }
         writer.write_all(tail.as_bytes())
     }
     fn create_shared_strings(&mut self, writer: &mut dyn Write) -> Result<()> {
@@ -565,13 +1403,16 @@ impl Workbook {
         writer.write_all(xml.as_bytes())?;
         writer.write_all(sst.as_bytes())?;
         // might have to use a vector instead to ensure index
-        for sf in shared_strings.strings.iter() {
+        for (sf, ruby) in shared_strings.strings.iter().zip(shared_strings.phonetics.iter()) {
             let space = if sf.trim().len() < sf.len() {
                 "t xml:space=\"preserve\""
             } else {
                 "t"
             };
-            let xmls = format!("<si><{}>{}</{}></si>", space, sf, "t");
+            let xmls = match ruby {
+                Some(ruby) => format!("<si><{}>{}</{}>{}</si>", space, sf, "t", crate::sheet::phonetic_run(sf, ruby)),
+                None => format!("<si><{}>{}</{}></si>", space, sf, "t"),
+            };
             writer.write_all(xmls.as_bytes())?;
         }
 
@@ -581,21 +1422,25 @@ impl Workbook {
     fn create_xl_rels(&mut self, writer: &mut dyn Write) -> Result<()> {
         let xml = br#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
 <Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships">
-<Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/theme" Target="theme/theme1.xml"/>
 <Relationship Id="rId2" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/styles" Target="styles.xml"/>
         "#;
         let tail = br#"
 </Relationships>
         "#;
         writer.write_all(xml)?;
+        if !self.omit_theme {
+            writer.write_all(br#"<Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/theme" Target="theme/theme1.xml"/>"#)?;
+        }
         let mut rid = 0;
         for sf in self.sheets.iter() {
             let str = format!("<Relationship Id=\"rId{}\" Type=\"http://schemas.openxmlformats.org/officeDocument/2006/relationships/worksheet\" Target=\"worksheets/sheet{}.xml\"/>", sf.id + 2, sf.id);
             writer.write_all(str.as_bytes())?;
             rid = sf.id + 2;
         }
-        let ss = format!("<Relationship Id=\"rId{}\" Type=\"http://schemas.openxmlformats.org/officeDocument/2006/relationships/sharedStrings\" Target=\"sharedStrings.xml\"/>", rid + 1);
-        writer.write_all(ss.as_bytes())?;
+        if self.shared_strings.used() {
+            let ss = format!("<Relationship Id=\"rId{}\" Type=\"http://schemas.openxmlformats.org/officeDocument/2006/relationships/sharedStrings\" Target=\"sharedStrings.xml\"/>", rid + 1);
+            writer.write_all(ss.as_bytes())?;
+        }
         writer.write_all(tail)
     }
 
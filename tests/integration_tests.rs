@@ -71,6 +71,12 @@ fn get_file_as_str_from_zip(mem_file: &Vec<u8>, file_name: &str) -> String {
     std::str::from_utf8(&temp_buf[..]).unwrap().to_string()
 }
 
+/// The names of the files in the archive, in archive order.
+fn archive_file_names(mem_file: &[u8]) -> Vec<String> {
+    let mut archive = zip::read::ZipArchive::new(Cursor::new(mem_file)).unwrap();
+    (0..archive.len()).map(|i| archive.by_index(i).unwrap().name().to_string()).collect()
+}
+
 #[test]
 fn creates_and_saves_an_excel_sheet() {
     let file_test = creates_and_saves_an_excel_sheet_driver(Some("test.xlsx"));
@@ -236,4 +242,1419 @@ fn chrono_check_default_style () {
     let expected_datetime_format_idx = 2;
     assert!(sheet1.contains(format!("<c r=\"A2\" s=\"{}\"><v>41223</v></c>", expected_date_format_idx).as_str()), "Date contains correct reference to date format");
     assert!(sheet1.contains(format!("<c r=\"B2\" s=\"{}\"><v>41890.88384259259</v></c>", expected_datetime_format_idx).as_str()), "Date contains correct reference to date format");
-}
\ No newline at end of file
+}
+#[test]
+fn writes_default_and_custom_page_margins() {
+    let mut wb = excel::Workbook::create_in_memory();
+    let mut ws = wb.create_sheet("default_margins");
+    wb.write_sheet(&mut ws, |sw| sw.append_row(row!["a"])).expect("Write excel error!");
+
+    let mut ws = wb.create_sheet("custom_margins");
+    ws.set_margins(0.5, 0.5, 1.0, 1.0, 0.25, 0.25);
+    wb.write_sheet(&mut ws, |sw| sw.append_row(row!["a"])).expect("Write excel error!");
+
+    let mem_file = wb.close().expect("No error on workbook close!").expect("Should have file in memory!");
+    let sheet1 = get_file_as_str_from_zip(&mem_file, "xl/worksheets/sheet1.xml");
+    assert!(sheet1.contains(r#"<pageMargins left="0.7" right="0.7" top="0.75" bottom="0.75" header="0.3" footer="0.3"/>"#));
+    let sheet2 = get_file_as_str_from_zip(&mem_file, "xl/worksheets/sheet2.xml");
+    assert!(sheet2.contains(r#"<pageMargins left="0.5" right="0.5" top="1" bottom="1" header="0.25" footer="0.25"/>"#));
+}
+
+#[test]
+fn writes_manual_page_breaks() {
+    let mut wb = excel::Workbook::create_in_memory();
+    let mut ws = wb.create_sheet("breaks");
+    ws.add_row_page_break(20);
+    ws.add_row_page_break(10);
+    ws.add_row_page_break(1);
+    ws.add_col_page_break(3);
+    wb.write_sheet(&mut ws, |sw| sw.append_row(row!["a"])).expect("Write excel error!");
+
+    let mem_file = wb.close().expect("No error on workbook close!").expect("Should have file in memory!");
+    let sheet1 = get_file_as_str_from_zip(&mem_file, "xl/worksheets/sheet1.xml");
+    assert!(sheet1.contains(r#"<rowBreaks count="2" manualBreakCount="2"><brk id="9" max="16383" man="1"/><brk id="19" max="16383" man="1"/></rowBreaks>"#));
+    assert!(sheet1.contains(r#"<colBreaks count="1" manualBreakCount="1"><brk id="2" max="1048575" man="1"/></colBreaks>"#));
+}
+
+#[test]
+fn to_bytes_can_be_called_before_close() {
+    let mut wb = excel::Workbook::create_in_memory();
+    let mut ws = wb.create_sheet("first");
+    wb.write_sheet(&mut ws, |sw| sw.append_row(row!["a"])).expect("Write excel error!");
+
+    let snapshot = wb.to_bytes().expect("Should build the archive!");
+    assert_eq!(snapshot, wb.to_bytes().expect("Should build the archive again!"));
+
+    let mut ws = wb.create_sheet("second");
+    wb.write_sheet(&mut ws, |sw| sw.append_row(row!["b"])).expect("Write excel error!");
+
+    let mem_file = wb.close().expect("No error on workbook close!").expect("Should have file in memory!");
+    let mut names = archive_file_names(&mem_file);
+    let count = names.len();
+    names.sort();
+    names.dedup();
+    assert_eq!(count, names.len(), "No part should be written twice");
+    assert!(names.contains(&"xl/worksheets/sheet2.xml".to_string()));
+}
+
+#[test]
+fn returns_written_sheet_xml() {
+    let mut wb = excel::Workbook::create_in_memory();
+    let mut ws = wb.create_sheet("data/2020");
+    assert!(wb.sheet_xml("data/2020").is_none(), "Sheet hasn't been written yet");
+
+    wb.write_sheet(&mut ws, |sw| sw.append_row(row!["Name", 1.5])).expect("Write excel error!");
+    let xml = wb.sheet_xml("data/2020").expect("Sheet should have been written");
+    assert!(xml.contains(r#"<c r="B1"><v>1.5</v></c>"#));
+    assert!(wb.sheet_xml("missing").is_none());
+}
+
+#[test]
+fn compact_mode_writes_minimal_xml() {
+    let mut wb = excel::Workbook::create_in_memory();
+    wb.compact();
+    let mut ws = wb.create_sheet("compact");
+    ws.add_column(Column { width: 10.0 });
+    ws.add_auto_filter(1, 2, 1, 2);
+    wb.write_sheet(&mut ws, |sw| {
+        sw.append_row(row!["a", 1.0])?;
+        sw.append_row(row!["  padded  ", 2.0])
+    })
+    .expect("Write excel error!");
+
+    let sheet1 = wb.sheet_xml("compact").unwrap();
+    assert!(!sheet1.contains('\n'), "Worksheet should contain no newlines: {}", sheet1);
+    assert!(sheet1.contains(r#"<sheetData><row r="1" spans="1:2"><c r="A1" t="str"><v>a</v></c><c r="B1"><v>1</v></c></row>"#));
+    assert!(sheet1.contains("<v>  padded  </v>"), "Cell text must be left untouched");
+
+    let mem_file = wb.close().expect("No error on workbook close!").expect("Should have file in memory!");
+    let styles = get_file_as_str_from_zip(&mem_file, "xl/styles.xml");
+    assert!(!styles.contains("\n        <"), "Style sheet should not be indented: {}", styles);
+    assert!(styles.contains(r#"</fonts><fills count="2">"#));
+}
+
+#[test]
+fn append_row_indexed_returns_row_number() {
+    let mut wb = excel::Workbook::create_in_memory();
+    let mut ws = wb.create_sheet("indexed");
+    wb.write_sheet(&mut ws, |sw| {
+        assert_eq!(1, sw.append_row_indexed(row!["header"])?);
+        sw.append_blank_rows(2);
+        assert_eq!(4, sw.append_row_indexed(row!["body"])?);
+        Ok(())
+    })
+    .expect("Write excel error!");
+}
+
+#[test]
+fn writes_numeric_and_array_formulas() {
+    let mut wb = excel::Workbook::create_in_memory();
+    let mut ws = wb.create_sheet("formulas");
+    wb.write_sheet(&mut ws, |sw| {
+        sw.append_row(row![
+            "=A2&B2",
+            CellValue::NumericFormula("SUM(A3:A5)".to_string()),
+            CellValue::ArrayFormula {
+                formula: "A3:A5*2".to_string(),
+                range: "C1:C3".to_string()
+            }
+        ])
+    })
+    .expect("Write excel error!");
+
+    let xml = wb.sheet_xml("formulas").unwrap();
+    assert!(xml.contains(r#"<c r="A1" t="str"><f>=A2&amp;B2</f></c>"#));
+    assert!(xml.contains(r#"<c r="B1"><f>SUM(A3:A5)</f></c>"#));
+    assert!(xml.contains(r#"<c r="C1"><f t="array" ref="C1:C3">A3:A5*2</f></c>"#));
+}
+
+#[test]
+fn text_macro_never_creates_formulas() {
+    let mut wb = excel::Workbook::create_in_memory();
+    let mut ws = wb.create_sheet("text");
+    wb.write_sheet(&mut ws, |sw| {
+        sw.append_row(row![text!("=not a formula"), CellValue::String("=literal".to_string()), "=A1"])
+    })
+    .expect("Write excel error!");
+
+    let xml = wb.sheet_xml("text").unwrap();
+    assert!(xml.contains(r#"<c r="A1" t="str"><v>=not a formula</v></c>"#));
+    assert!(xml.contains(r#"<c r="B1" t="str"><v>=literal</v></c>"#));
+    assert!(xml.contains(r#"<c r="C1" t="str"><f>=A1</f></c>"#));
+}
+
+#[test]
+fn counts_written_rows_and_columns() {
+    let mut wb = excel::Workbook::create_in_memory();
+    let mut ws = wb.create_sheet("counts");
+    assert_eq!((0, 0), (ws.row_count(), ws.column_count()));
+    wb.write_sheet(&mut ws, |sw| {
+        sw.append_row(row!["a", "b"])?;
+        sw.append_row(row!["a", blank!(3), "e", blank!(2)])?;
+        sw.append_blank_rows(2);
+        sw.append_row(row!["a"])
+    })
+    .expect("Write excel error!");
+    assert_eq!(5, ws.row_count());
+    assert_eq!(5, ws.column_count());
+}
+
+#[test]
+fn writes_split_panes() {
+    let mut wb = excel::Workbook::create_in_memory();
+    let mut ws = wb.create_sheet("split");
+    ws.split_panes(2400.0, 1200.0);
+    wb.write_sheet(&mut ws, |sw| sw.append_row(row!["a"])).expect("Write excel error!");
+
+    let mut ws = wb.create_sheet("horizontal");
+    ws.split_panes(0.0, 600.0);
+    wb.write_sheet(&mut ws, |sw| sw.append_row(row!["a"])).expect("Write excel error!");
+
+    let xml = wb.sheet_xml("split").unwrap();
+    assert!(xml.contains(r#"<sheetViews><sheetView workbookViewId="0"><pane xSplit="2400" ySplit="1200" activePane="bottomRight" state="split"/></sheetView></sheetViews>"#));
+    let xml = wb.sheet_xml("horizontal").unwrap();
+    assert!(xml.contains(r#"<pane ySplit="600" activePane="bottomLeft" state="split"/>"#));
+}
+
+#[test]
+fn writes_frozen_panes_with_selections() {
+    let mut wb = excel::Workbook::create_in_memory();
+    let mut ws = wb.create_sheet("top row");
+    ws.freeze_panes(1, 0);
+    wb.write_sheet(&mut ws, |sw| sw.append_row(row!["a"])).expect("Write excel error!");
+
+    let mut ws = wb.create_sheet("corner");
+    ws.freeze_panes(2, 1);
+    wb.write_sheet(&mut ws, |sw| sw.append_row(row!["a"])).expect("Write excel error!");
+
+    let xml = wb.sheet_xml("top row").unwrap();
+    assert!(xml.contains(r#"<sheetView workbookViewId="0"><pane ySplit="1" topLeftCell="A2" activePane="bottomLeft" state="frozen"/><selection pane="bottomLeft" activeCell="A2" sqref="A2"/></sheetView>"#), "{}", xml);
+    let xml = wb.sheet_xml("corner").unwrap();
+    assert!(xml.contains(r#"<pane xSplit="1" ySplit="2" topLeftCell="B3" activePane="bottomRight" state="frozen"/><selection pane="topRight" activeCell="B1" sqref="B1"/><selection pane="bottomLeft" activeCell="A3" sqref="A3"/><selection pane="bottomRight" activeCell="B3" sqref="B3"/></sheetView>"#), "{}", xml);
+}
+
+#[test]
+fn adds_grouped_number_formats() {
+    let mut wb = excel::Workbook::create_in_memory();
+    assert_eq!(3, wb.add_grouped_number_format(0));
+    assert_eq!(4, wb.add_grouped_number_format(2));
+    let mut ws = wb.create_sheet("grouped");
+    wb.write_sheet(&mut ws, |sw| sw.append_row(row![(1234.5, 4)])).expect("Write excel error!");
+
+    let mem_file = wb.close().expect("No error on workbook close!").expect("Should have file in memory!");
+    let styles = get_file_as_str_from_zip(&mem_file, "xl/styles.xml");
+    assert!(styles.contains(r##"<numFmt numFmtId="165" formatCode="#,##0"/>"##));
+    assert!(styles.contains(r##"<numFmt numFmtId="166" formatCode="#,##0.00"/>"##));
+}
+
+#[test]
+fn skipped_cells_keep_references() {
+    let mut wb = excel::Workbook::create_in_memory();
+    let mut ws = wb.create_sheet("sparse");
+    wb.write_sheet(&mut ws, |sw| {
+        let mut row = Row::new();
+        row.add_cell("A");
+        row.skip(1);
+        row.add_cell("C");
+        row.skip(2);
+        row.add_cell("F");
+        sw.append_row(row)
+    })
+    .expect("Write excel error!");
+
+    let xml = wb.sheet_xml("sparse").unwrap();
+    assert!(xml.contains(r#"<c r="A1" t="str"><v>A</v></c><c r="C1" t="str"><v>C</v></c><c r="F1" t="str"><v>F</v></c>"#));
+}
+
+#[test]
+fn transforms_sheet_xml_before_archiving() {
+    let mut wb = excel::Workbook::create_in_memory();
+    let mut ws = wb.create_sheet("transformed");
+    wb.with_sheet_xml_transform(ws.id, |xml| {
+        let text = String::from_utf8(xml.clone()).unwrap();
+        *xml = text.replace("<sheetData>", "<sheetData><!-- injected -->").into_bytes();
+    });
+    wb.write_sheet(&mut ws, |sw| sw.append_row(row!["a"])).expect("Write excel error!");
+
+    let xml = wb.sheet_xml("transformed").unwrap();
+    assert!(xml.contains("<sheetData><!-- injected -->"));
+}
+
+#[test]
+fn writes_shared_formula_column() {
+    let mut wb = excel::Workbook::create_in_memory();
+    let mut ws = wb.create_sheet("shared");
+    wb.write_sheet(&mut ws, |sw| {
+        sw.append_row(row!["A", "B", "A*B"])?;
+        assert!(sw.append_shared_formula_column(3, 1, 3, "A1*B1").is_err(), "Row 1 is already written");
+        sw.append_shared_formula_column(3, 2, 3, "A2*B2")?;
+        sw.append_shared_formula_column(3, 6, 1, "A6*B6")
+    })
+    .expect("Write excel error!");
+
+    let xml = wb.sheet_xml("shared").unwrap();
+    assert!(xml.contains(r#"<c r="C2"><f t="shared" ref="C2:C4" si="0">A2*B2</f></c>"#));
+    assert!(xml.contains(r#"<c r="C3"><f t="shared" si="0"/></c>"#));
+    assert!(xml.contains(r#"<c r="C4"><f t="shared" si="0"/></c>"#));
+    assert!(!xml.contains(r#"<row r="5">"#));
+    assert!(xml.contains(r#"<c r="C6"><f t="shared" ref="C6:C6" si="1">A6*B6</f></c>"#));
+    assert_eq!(6, ws.row_count());
+}
+
+#[test]
+fn shared_strings_part_only_when_used() {
+    let path = std::env::temp_dir().join("simple_excel_writer_no_shared_strings.xlsx");
+    {
+        let mut wb = excel::Workbook::create_simple(path.to_str().unwrap());
+        let mut ws = wb.create_sheet("simple");
+        wb.write_sheet(&mut ws, |sw| sw.append_row(row!["a"])).expect("Write excel error!");
+        wb.close().expect("Close excel error!");
+    }
+    let mem_file = std::fs::read(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert!(!archive_file_names(&mem_file).contains(&"xl/sharedStrings.xml".to_string()));
+    assert!(!get_file_as_str_from_zip(&mem_file, "[Content_Types].xml").contains("sharedStrings"));
+    assert!(!get_file_as_str_from_zip(&mem_file, "xl/_rels/workbook.xml.rels").contains("sharedStrings"));
+
+    let path = std::env::temp_dir().join("simple_excel_writer_shared_strings.xlsx");
+    {
+        let mut wb = excel::Workbook::create(path.to_str().unwrap());
+        let mut ws = wb.create_sheet("shared");
+        wb.write_sheet(&mut ws, |sw| sw.append_row(row!["a"])).expect("Write excel error!");
+        wb.close().expect("Close excel error!");
+    }
+    let mem_file = std::fs::read(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert!(archive_file_names(&mem_file).contains(&"xl/sharedStrings.xml".to_string()));
+    assert!(get_file_as_str_from_zip(&mem_file, "[Content_Types].xml").contains("/xl/sharedStrings.xml"));
+    assert!(get_file_as_str_from_zip(&mem_file, "xl/_rels/workbook.xml.rels").contains("sharedStrings.xml"));
+}
+
+#[test]
+fn empty_workbook_gets_a_blank_sheet() {
+    let mut wb = excel::Workbook::create_in_memory();
+    let mem_file = wb.close().expect("No error on workbook close!").expect("Should have file in memory!");
+    assert!(archive_file_names(&mem_file).contains(&"xl/worksheets/sheet1.xml".to_string()));
+    let workbook = get_file_as_str_from_zip(&mem_file, "xl/workbook.xml");
+    assert!(workbook.contains(r#"<sheet name="Sheet1" sheetId="1" r:id="rId3"/>"#));
+}
+
+#[test]
+fn to_bytes_on_an_empty_workbook_leaves_it_empty() {
+    let mut wb = excel::Workbook::create_in_memory();
+    let snapshot = wb.to_bytes().expect("Should build the archive!");
+    assert!(archive_file_names(&snapshot).contains(&"xl/worksheets/sheet1.xml".to_string()));
+    assert!(get_file_as_str_from_zip(&snapshot, "xl/workbook.xml").contains(r#"<sheet name="Sheet1""#));
+
+    let mut ws = wb.create_sheet("Data");
+    wb.write_sheet(&mut ws, |sw| sw.append_row(row!["a"])).expect("Write excel error!");
+    let mem_file = wb.to_bytes().expect("Should build the archive!");
+    let workbook = get_file_as_str_from_zip(&mem_file, "xl/workbook.xml");
+    assert!(workbook.contains(r#"<sheet name="Data" sheetId="1" r:id="rId3"/>"#), "{}", workbook);
+    assert!(!workbook.contains("Sheet1"), "{}", workbook);
+    assert!(!get_file_as_str_from_zip(&mem_file, "[Content_Types].xml").contains("sheet2.xml"));
+    assert!(!get_file_as_str_from_zip(&mem_file, "docProps/app.xml").contains("Sheet1"));
+}
+
+#[test]
+fn sets_application_name() {
+    let mut wb = excel::Workbook::create_in_memory();
+    let mem_file = wb.to_bytes().expect("Should build the archive!");
+    assert!(get_file_as_str_from_zip(&mem_file, "docProps/app.xml").contains("<Application>SheetJS</Application>"));
+
+    wb.set_application_name("Reports & Co");
+    let mut ws = wb.create_sheet("Q&A");
+    wb.write_sheet(&mut ws, |sw| sw.append_row(row!["x"])).expect("Write excel error!");
+    let mem_file = wb.to_bytes().expect("Should build the archive!");
+    let app = get_file_as_str_from_zip(&mem_file, "docProps/app.xml");
+    assert!(app.contains("<Application>Reports &amp; Co</Application>"), "{}", app);
+    assert!(app.contains("<vt:lpstr>Q&amp;A</vt:lpstr>"), "{}", app);
+    assert!(!app.contains("SheetJS"), "{}", app);
+}
+
+#[test]
+fn writes_a_sheet_across_several_calls() {
+    let path = std::env::temp_dir().join("simple_excel_writer_open_sheet.xlsx");
+    let mut wb = excel::Workbook::create(path.to_string_lossy().into_owned());
+    let mut ws = wb.create_sheet("incremental");
+    wb.open_sheet(&mut ws).unwrap().append_row(row!["Name", "Name"]).unwrap();
+    {
+        let mut sw = wb.open_sheet(&mut ws).unwrap();
+        sw.append_row(row!["Amy", "Bob"]).unwrap();
+        sw.append_row(row!["Name", "Amy"]).unwrap();
+    }
+    assert!(wb.to_bytes().is_err(), "An open sheet must be finished first");
+    wb.finish_sheet(&mut ws).unwrap();
+    assert!(wb.finish_sheet(&mut ws).is_err(), "The sheet is already finished");
+
+    let xml = wb.sheet_xml("incremental").unwrap();
+    assert_eq!(1, xml.matches("<sheetData>").count());
+    assert!(xml.contains(r#"<row r="1" spans="1:2">"#));
+    assert!(xml.contains(r#"<c r="B3" t="s"><v>1</v></c>"#), "Shared strings should carry over: {}", xml);
+    assert!(xml.ends_with("</worksheet>\n"));
+    assert_eq!(3, ws.row_count());
+
+    let mem_file = wb.to_bytes().unwrap();
+    let shared = get_file_as_str_from_zip(&mem_file, "xl/sharedStrings.xml");
+    assert!(shared.contains(r#"count="6" uniqueCount="3""#), "{}", shared);
+    wb.close().expect("Close excel error!");
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn sheet_can_opt_out_of_shared_strings() {
+    let path = std::env::temp_dir().join("simple_excel_writer_inline_strings.xlsx");
+    let mut wb = excel::Workbook::create(path.to_str().unwrap());
+    let mut lookup = wb.create_sheet("lookup");
+    wb.write_sheet(&mut lookup, |sw| sw.append_row(row!["a"])).expect("Write excel error!");
+    let mut data = wb.create_sheet("data");
+    data.use_inline_strings();
+    wb.write_sheet(&mut data, |sw| sw.append_row(row!["a"])).expect("Write excel error!");
+
+    assert!(wb.sheet_xml("lookup").unwrap().contains(r#"<c r="A1" t="s"><v>0</v></c>"#));
+    assert!(wb.sheet_xml("data").unwrap().contains(r#"<c r="A1" t="str"><v>a</v></c>"#));
+    wb.close().expect("Close excel error!");
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn writes_raw_cells_verbatim() {
+    let mut wb = excel::Workbook::create_in_memory();
+    let mut ws = wb.create_sheet("raw");
+    wb.write_sheet(&mut ws, |sw| {
+        sw.append_row(row!["a", CellValue::Raw(r#"<c r="B1" cm="1"><v>1</v></c>"#.to_string()), "c"])
+    })
+    .expect("Write excel error!");
+
+    let xml = wb.sheet_xml("raw").unwrap();
+    assert!(xml.contains(r#"<c r="A1" t="str"><v>a</v></c><c r="B1" cm="1"><v>1</v></c><c r="C1" t="str"><v>c</v></c>"#));
+}
+
+#[test]
+fn writes_calc_properties() {
+    let mut wb = excel::Workbook::create_in_memory();
+    let mut ws = wb.create_sheet("calc");
+    wb.write_sheet(&mut ws, |sw| sw.append_row(row![1.0, "=A1*2"]))
+        .expect("Write excel error!");
+    wb.set_calc_properties(excel::CalcMode::Manual, true, 100);
+
+    let bytes = wb.close().expect("Close excel error!").unwrap();
+    let xml = get_file_as_str_from_zip(&bytes, "xl/workbook.xml");
+    assert!(xml.contains(r#"</sheets>
+    <calcPr calcMode="manual" iterate="1" iterateCount="100"/>"#));
+
+    let mut wb = excel::Workbook::create_in_memory();
+    wb.set_full_calc_on_load(true);
+    let xml = get_file_as_str_from_zip(&wb.to_bytes().unwrap(), "xl/workbook.xml");
+    assert!(xml.contains(r#"<calcPr fullCalcOnLoad="1"/>"#), "{}", xml);
+    wb.set_calc_properties(excel::CalcMode::Automatic, false, 0);
+    let xml = get_file_as_str_from_zip(&wb.to_bytes().unwrap(), "xl/workbook.xml");
+    assert!(xml.contains(r#"<calcPr calcMode="auto" fullCalcOnLoad="1"/>"#), "{}", xml);
+}
+
+#[test]
+fn writes_hyperlinks_with_tooltips() {
+    let mut wb = excel::Workbook::create_in_memory();
+    let mut ws = wb.create_sheet("links");
+    wb.write_sheet(&mut ws, |sw| {
+        sw.append_row(row!["Docs", "Search"])?;
+        sw.add_hyperlink((1, 1), "https://example.com/?a=1&b=2", Some("<docs>"), Some("Open \"the\" docs"))?;
+        sw.add_hyperlink((2, 1), "https://example.com/search", None, None)?;
+        assert!(sw.add_hyperlink((0, 1), "https://example.com", None, None).is_err());
+        Ok(())
+    })
+    .expect("Write excel error!");
+
+    let xml = wb.sheet_xml("links").unwrap();
+    assert!(xml.contains(r#"<hyperlinks><hyperlink ref="A1" r:id="rId1" display="&lt;docs&gt;" tooltip="Open &quot;the&quot; docs"/><hyperlink ref="B1" r:id="rId2"/></hyperlinks><pageMargins"#), "{}", xml);
+
+    let bytes = wb.close().expect("Close excel error!").unwrap();
+    let rels = get_file_as_str_from_zip(&bytes, "xl/worksheets/_rels/sheet1.xml.rels");
+    assert!(rels.contains(r#"<Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/hyperlink" Target="https://example.com/?a=1&amp;b=2" TargetMode="External"/>"#), "{}", rels);
+    assert!(rels.contains(r#"Id="rId2""#));
+}
+
+#[test]
+fn writes_phonetic_strings() {
+    let phonetic = |base: &str, ruby: &str| CellValue::Phonetic { base: base.to_string(), ruby: ruby.to_string() };
+
+    let path = std::env::temp_dir().join("simple_excel_writer_phonetic.xlsx");
+    let mut wb = excel::Workbook::create(path.to_str().unwrap());
+    let mut ws = wb.create_sheet("shared");
+    wb.write_sheet(&mut ws, |sw| {
+        sw.append_row(row![phonetic("漢字", "かんじ"), "漢字", phonetic("漢字", "かんじ"), phonetic("A&B", "えい")])
+    })
+    .expect("Write excel error!");
+    let mem_file = wb.to_bytes().unwrap();
+    wb.close().expect("Close excel error!");
+    std::fs::remove_file(&path).unwrap();
+
+    let xml = wb.sheet_xml("shared").unwrap();
+    assert!(xml.contains(r#"<c r="C1" t="s"><v>0</v></c>"#), "{}", xml);
+    let shared = get_file_as_str_from_zip(&mem_file, "xl/sharedStrings.xml");
+    assert!(shared.contains(r#"count="4" uniqueCount="3""#), "{}", shared);
+    assert!(shared.contains(r#"<si><t>漢字</t><rPh sb="0" eb="2"><t>かんじ</t></rPh><phoneticPr fontId="0"/></si><si><t>漢字</t></si>"#), "{}", shared);
+    assert!(shared.contains(r#"<si><t>A&amp;B</t><rPh sb="0" eb="3"><t>えい</t></rPh>"#), "{}", shared);
+
+    let mut wb = excel::Workbook::create_in_memory();
+    let mut ws = wb.create_sheet("inline");
+    wb.write_sheet(&mut ws, |sw| sw.append_row(row![phonetic("漢字", "かんじ")])).expect("Write excel error!");
+    let xml = wb.sheet_xml("inline").unwrap();
+    assert!(xml.contains(r#"<c r="A1" t="inlineStr"><is><t>漢字</t><rPh sb="0" eb="2"><t>かんじ</t></rPh><phoneticPr fontId="0"/></is></c>"#), "{}", xml);
+}
+
+#[test]
+fn adds_data_sheets() {
+    let mut wb = excel::Workbook::create_in_memory();
+    wb.add_data_sheet("data", &[&["Name", "Formula"], &["Amy", "=1+1", "extra"], &[], &["", "Bob"]])
+        .expect("Write excel error!");
+    wb.add_data_sheet("empty", &[]).expect("Write excel error!");
+
+    let xml = wb.sheet_xml("data").unwrap();
+    assert!(xml.contains(r#"<c r="B2" t="str"><v>=1+1</v></c><c r="C2" t="str"><v>extra</v></c>"#), "{}", xml);
+    assert!(xml.contains(r#"<row r="3">"#));
+    assert!(xml.contains(r#"<row r="4" spans="2:2">
+<c r="B4" t="str"><v>Bob</v></c>"#), "{}", xml);
+    assert!(wb.sheet_xml("empty").unwrap().contains("<sheetData>"));
+}
+
+#[test]
+fn stores_small_parts_uncompressed() {
+    let methods = |bytes: &Vec<u8>| {
+        let mut archive = zip::read::ZipArchive::new(Cursor::new(bytes)).unwrap();
+        (0..archive.len())
+            .map(|i| {
+                let file = archive.by_index(i).unwrap();
+                (file.name().to_string(), file.size(), file.compression())
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let mut wb = excel::Workbook::create_in_memory();
+    let all_deflated = methods(&wb.to_bytes().unwrap());
+    assert!(all_deflated.iter().all(|f| f.2 == zip::CompressionMethod::Deflated));
+
+    wb.set_min_compress_size(1024);
+    let files = methods(&wb.to_bytes().unwrap());
+    assert!(files.iter().any(|f| f.2 == zip::CompressionMethod::Stored));
+    for (name, size, method) in files {
+        let expected = if size < 1024 { zip::CompressionMethod::Stored } else { zip::CompressionMethod::Deflated };
+        assert_eq!(expected, method, "{} ({} bytes)", name, size);
+    }
+}
+
+#[test]
+fn reads_back_sheet_values() {
+    let path = std::env::temp_dir().join("simple_excel_writer_read_back.xlsx");
+    let mut wb = excel::Workbook::create(path.to_str().unwrap());
+    let mut ws = wb.create_sheet("Data & more");
+    wb.write_sheet(&mut ws, |sw| {
+        sw.append_row(row!["Name", "Active", "Score"])?;
+        sw.append_row(row!["<Amy>", true, 1.5, (), "=C2*2"])?;
+        sw.append_blank_rows(1);
+        sw.append_row(row![blank!(1), false, CellValue::Phonetic { base: "漢字".to_string(), ruby: "かんじ".to_string() }])
+    })
+    .expect("Write excel error!");
+    let mut ws = wb.create_sheet("Inline");
+    ws.use_inline_strings();
+    wb.write_sheet(&mut ws, |sw| sw.append_row(row!["a & b", 2.0])).expect("Write excel error!");
+    let bytes = wb.to_bytes().unwrap();
+    wb.close().expect("Close excel error!");
+    std::fs::remove_file(&path).unwrap();
+
+    let values = excel::Workbook::read_sheet_values(&bytes, "Data & more").unwrap();
+    assert_eq!(
+        vec![
+            vec!["Name", "Active", "Score"],
+            vec!["<Amy>", "TRUE", "1.5", "", ""],
+            vec![],
+            vec!["", "FALSE", "漢字"],
+        ],
+        values
+    );
+    let values = excel::Workbook::read_sheet_values(&bytes, "Inline").unwrap();
+    assert_eq!(vec![vec!["a & b", "2"]], values);
+    let err = excel::Workbook::read_sheet_values(&bytes, "Missing").unwrap_err();
+    assert_eq!(std::io::ErrorKind::NotFound, err.kind());
+}
+
+#[test]
+fn writes_base_column_width() {
+    let mut wb = excel::Workbook::create_in_memory();
+    let mut ws = wb.create_sheet("base");
+    ws.set_base_col_width(12);
+    ws.add_column(Column { width: 30.0 });
+    wb.write_sheet(&mut ws, |sw| sw.append_row(row!["a"])).expect("Write excel error!");
+
+    let xml = wb.sheet_xml("base").unwrap();
+    let format = xml.find(r#"<sheetFormatPr baseColWidth="12" defaultRowHeight="15"/>"#).expect("sheetFormatPr");
+    assert!(format < xml.find("<cols>").unwrap());
+}
+
+#[test]
+fn adds_number_formats_while_writing() {
+    let mut wb = excel::Workbook::create_in_memory();
+    let euro = wb.add_cust_number_format("\"€\"#,##0.00".to_string());
+    let mut ws = wb.create_sheet("formats");
+    wb.write_sheet(&mut ws, |sw| {
+        for (value, unit) in &[(1.5, "kg"), (2.0, "m"), (3.0, "kg")] {
+            let fmt = sw.add_cust_number_format(&format!("0.0\" {}\"", unit))?;
+            sw.append_row(row![(*value, fmt)])?;
+        }
+        assert_eq!(euro, sw.add_cust_number_format("\"€\"#,##0.00")?);
+        Ok(())
+    })
+    .expect("Write excel error!");
+
+    let xml = wb.sheet_xml("formats").unwrap();
+    assert!(xml.contains(r#"<c r="A1" s="4"><v>1.5</v></c>"#), "{}", xml);
+    assert!(xml.contains(r#"<c r="A2" s="5"><v>2</v></c>"#), "{}", xml);
+    assert!(xml.contains(r#"<c r="A3" s="4"><v>3</v></c>"#), "{}", xml);
+
+    let bytes = wb.to_bytes().unwrap();
+    let styles = get_file_as_str_from_zip(&bytes, "xl/styles.xml");
+    assert!(styles.contains(r#"<numFmts count="3">"#), "{}", styles);
+
+    let mut buf = Vec::new();
+    let mut sheet = excel::Sheet::new(1, "detached");
+    let mut shared = excel::SharedStrings::new_unused();
+    let mut sw = excel::SheetWriter::new(&mut sheet, &mut buf, &mut shared);
+    assert!(sw.add_cust_number_format("0.0").is_err());
+}
+
+#[test]
+fn writes_print_options() {
+    let mut wb = excel::Workbook::create_in_memory();
+    let mut ws = wb.create_sheet("centered");
+    ws.set_print_centered(true, true);
+    wb.write_sheet(&mut ws, |sw| {
+        sw.append_row(row!["a", "b"])?;
+        sw.merge_cells((1, 1), (2, 1))
+    })
+    .expect("Write excel error!");
+    let mut ws = wb.create_sheet("plain");
+    wb.write_sheet(&mut ws, |sw| sw.append_row(row!["a"])).expect("Write excel error!");
+    let mut ws = wb.create_sheet("grid");
+    ws.print_gridlines(true);
+    wb.write_sheet(&mut ws, |sw| sw.append_row(row!["a"])).expect("Write excel error!");
+
+    let xml = wb.sheet_xml("grid").unwrap();
+    assert!(xml.contains(r#"<printOptions gridLines="1"/><pageMargins"#), "{}", xml);
+    let xml = wb.sheet_xml("centered").unwrap();
+    assert!(xml.contains(r#"</mergeCells><printOptions horizontalCentered="1" verticalCentered="1"/><pageMargins"#), "{}", xml);
+    assert!(!wb.sheet_xml("plain").unwrap().contains("<printOptions"));
+}
+
+#[test]
+fn sets_custom_height_only_on_rows_with_height() {
+    let mut wb = excel::Workbook::create_in_memory();
+    let mut ws = wb.create_sheet("heights");
+    wb.write_sheet(&mut ws, |sw| {
+        sw.append_row(row!["normal"])?;
+        sw.append_row_with_height(row!["tall"], 30.5)
+    })
+    .expect("Write excel error!");
+
+    let xml = wb.sheet_xml("heights").unwrap();
+    assert!(xml.contains("<row r=\"1\" spans=\"1:1\">\n"), "{}", xml);
+    assert!(xml.contains(r#"<row r="2" spans="1:1" ht="30.5" customHeight="1">"#), "{}", xml);
+    assert_eq!(1, xml.matches("customHeight").count());
+    assert_eq!(1, xml.matches(" ht=").count());
+}
+
+#[test]
+fn writes_data_bars() {
+    let mut wb = excel::Workbook::create_in_memory();
+    let mut ws = wb.create_sheet("bars");
+    ws.add_data_bar("A1:A3", 0xFF638EC6);
+    ws.add_data_bar("B1:B3", 0xFF00FF);
+    wb.write_sheet(&mut ws, |sw| sw.append_row(row![1.0, 2.0])).expect("Write excel error!");
+
+    let xml = wb.sheet_xml("bars").unwrap();
+    assert!(xml.contains(r#"<conditionalFormatting sqref="A1:A3"><cfRule type="dataBar" priority="1"><dataBar><cfvo type="min"/><cfvo type="max"/><color rgb="FF638EC6"/></dataBar></cfRule></conditionalFormatting>"#), "{}", xml);
+    assert!(xml.contains(r#"<conditionalFormatting sqref="B1:B3"><cfRule type="dataBar" priority="2"><dataBar><cfvo type="min"/><cfvo type="max"/><color rgb="00FF00FF"/>"#), "{}", xml);
+}
+
+#[test]
+fn writes_workbook_window() {
+    let mut wb = excel::Workbook::create_in_memory();
+    let xml = get_file_as_str_from_zip(&wb.to_bytes().unwrap(), "xl/workbook.xml");
+    assert!(!xml.contains("<bookViews>"));
+    assert!(xml.contains(r#"<workbookPr date1904="false"/>
+    <sheets>"#), "{}", xml);
+
+    wb.set_window(28800, 17600, 800);
+    let xml = get_file_as_str_from_zip(&wb.to_bytes().unwrap(), "xl/workbook.xml");
+    assert!(xml.contains(r#"<bookViews><workbookView xWindow="0" yWindow="0" windowWidth="28800" windowHeight="17600" tabRatio="800"/></bookViews><sheets>"#), "{}", xml);
+}
+
+#[test]
+fn sets_default_font_color() {
+    let mut wb = excel::Workbook::create_in_memory();
+    let styles = get_file_as_str_from_zip(&wb.to_bytes().unwrap(), "xl/styles.xml");
+    assert!(styles.contains(r#"<sz val="12"/>
+            <color theme="1"/>
+            <name val="Calibri"/>"#), "{}", styles);
+
+    wb.set_default_font_color(0xFF404040);
+    let styles = get_file_as_str_from_zip(&wb.to_bytes().unwrap(), "xl/styles.xml");
+    assert!(styles.contains(r#"<sz val="12"/>
+            <color rgb="FF404040"/>
+            <name val="Calibri"/>"#), "{}", styles);
+}
+
+#[test]
+fn writes_workbooks_without_theme() {
+    let mut wb = excel::Workbook::create_in_memory();
+    let bytes = wb.to_bytes().unwrap();
+    assert!(archive_file_names(&bytes).contains(&"xl/theme/theme1.xml".to_string()));
+    assert!(get_file_as_str_from_zip(&bytes, "xl/_rels/workbook.xml.rels").contains("theme1.xml"));
+
+    wb.without_theme();
+    let bytes = wb.to_bytes().unwrap();
+    assert!(!archive_file_names(&bytes).contains(&"xl/theme/theme1.xml".to_string()));
+    assert!(!get_file_as_str_from_zip(&bytes, "[Content_Types].xml").contains("theme"));
+    assert!(!get_file_as_str_from_zip(&bytes, "xl/_rels/workbook.xml.rels").contains("theme"));
+    let styles = get_file_as_str_from_zip(&bytes, "xl/styles.xml");
+    assert!(styles.contains(r#"<color rgb="FF000000"/>"#), "{}", styles);
+    assert!(!styles.contains("theme="));
+    assert!(!styles.contains("<scheme"));
+}
+
+#[test]
+fn interns_strings_up_front() {
+    let path = std::env::temp_dir().join("simple_excel_writer_intern.xlsx");
+    let mut wb = excel::Workbook::create(path.to_str().unwrap());
+    let fruit = wb.intern_string("Fruit & Veg");
+    let dairy = wb.intern_string("Dairy");
+    let mut ws = wb.create_sheet("categories");
+    wb.write_sheet(&mut ws, |sw| {
+        sw.append_row(row![fruit.clone(), "Apple"])?;
+        sw.append_row(row![fruit.clone(), "Dairy"])?;
+        sw.append_row(row![dairy.clone(), "Milk"])
+    })
+    .expect("Write excel error!");
+    let bytes = wb.to_bytes().unwrap();
+    wb.close().expect("Close excel error!");
+    std::fs::remove_file(&path).unwrap();
+
+    let shared = get_file_as_str_from_zip(&bytes, "xl/sharedStrings.xml");
+    assert!(shared.contains(r#"count="6" uniqueCount="4""#), "{}", shared);
+    assert!(shared.contains("<si><t>Fruit &amp; Veg</t></si><si><t>Dairy</t></si><si><t>Apple</t></si>"), "{}", shared);
+    let xml = wb.sheet_xml("categories").unwrap();
+    assert!(xml.contains(r#"<c r="A2" t="s"><v>0</v></c><c r="B2" t="s"><v>1</v></c>"#), "{}", xml);
+
+    let mut wb = excel::Workbook::create_in_memory();
+    match wb.intern_string("a") {
+        CellValue::String(s) => assert_eq!("a", s),
+        _ => panic!("expected a plain string without a shared string table"),
+    }
+}
+
+#[test]
+fn ignores_numbers_stored_as_text() {
+    let mut wb = excel::Workbook::create_in_memory();
+    let mut ws = wb.create_sheet("ids");
+    ws.add_row_page_break(2);
+    wb.write_sheet(&mut ws, |sw| {
+        sw.append_row(row!["01234", "5"])?;
+        sw.ignore_number_as_text("A1:A10");
+        sw.ignore_number_as_text("B1");
+        Ok(())
+    })
+    .expect("Write excel error!");
+
+    let xml = wb.sheet_xml("ids").unwrap();
+    assert!(xml.contains(r#"</rowBreaks><ignoredErrors><ignoredError sqref="A1:A10 B1" numberStoredAsText="1"/></ignoredErrors>"#), "{}", xml);
+}
+
+#[test]
+fn writes_booleans_as_text() {
+    let mut wb = excel::Workbook::create_in_memory();
+    let mut ws = wb.create_sheet("default");
+    wb.write_sheet(&mut ws, |sw| sw.append_row(row![true])).expect("Write excel error!");
+    wb.booleans_as_text();
+    let mut ws = wb.create_sheet("text");
+    wb.write_sheet(&mut ws, |sw| sw.append_row(row![true, false])).expect("Write excel error!");
+
+    assert!(wb.sheet_xml("default").unwrap().contains(r#"<c r="A1" t="b"><v>1</v></c>"#));
+    let xml = wb.sheet_xml("text").unwrap();
+    assert!(xml.contains(r#"<c r="A1" t="str"><v>TRUE</v></c><c r="B1" t="str"><v>FALSE</v></c>"#), "{}", xml);
+}
+
+#[test]
+fn writes_text_columns_as_strings() {
+    let mut wb = excel::Workbook::create_in_memory();
+    let mut ws = wb.create_sheet("ids");
+    ws.set_column_type(1, excel::ColumnType::Text);
+    ws.set_column_type(3, excel::ColumnType::Text);
+    ws.set_column_type(3, excel::ColumnType::General);
+    wb.write_sheet(&mut ws, |sw| {
+        sw.append_row(row![12345.0, 1.5, 2.0])?;
+        sw.append_row(row![true, false, "=A1"])?;
+        sw.append_row(row!["=B1", 3.0])?;
+        sw.append_row(row![excel::Currency::usd(19.99)])
+    })
+    .expect("Write excel error!");
+
+    let xml = wb.sheet_xml("ids").unwrap();
+    assert!(xml.contains(r#"<c r="A4" t="str"><v>19.99</v></c>"#), "{}", xml);
+    assert!(xml.contains(r#"<c r="A1" t="str"><v>12345</v></c><c r="B1"><v>1.5</v></c><c r="C1"><v>2</v></c>"#), "{}", xml);
+    assert!(xml.contains(r#"<c r="A2" t="str"><v>TRUE</v></c><c r="B2" t="b"><v>0</v></c>"#), "{}", xml);
+    assert!(xml.contains(r#"<c r="A3" t="str"><f>=B1</f></c>"#), "{}", xml);
+}
+
+#[test]
+fn writes_row_spans() {
+    let mut wb = excel::Workbook::create_in_memory();
+    let mut ws = wb.create_sheet("spans");
+    wb.write_sheet(&mut ws, |sw| {
+        sw.append_row(row!["a", (), "c"])?;
+        sw.append_row(row![blank!(29), "ad"])?;
+        sw.append_row(row![])
+    })
+    .expect("Write excel error!");
+
+    let xml = wb.sheet_xml("spans").unwrap();
+    assert!(xml.contains(r#"<row r="1" spans="1:3">"#), "{}", xml);
+    assert!(xml.contains(r#"<row r="2" spans="30:30">"#), "{}", xml);
+    assert!(xml.contains(r#"<row r="3">"#), "{}", xml);
+}
+
+#[test]
+fn omits_sequential_references() {
+    let mut wb = excel::Workbook::create_in_memory();
+    wb.compact();
+    wb.omit_cell_refs();
+    let mut ws = wb.create_sheet("refs");
+    wb.write_sheet(&mut ws, |sw| {
+        sw.append_row(row!["a", 1.0, (), true])?;
+        sw.append_row(row![blank!(1), 2.0])?;
+        sw.append_blank_rows(1);
+        sw.append_row(row![3.0])
+    })
+    .expect("Write excel error!");
+
+    let xml = wb.sheet_xml("refs").unwrap();
+    assert!(xml.contains(concat!(
+        r#"<sheetData><row spans="1:4"><c t="str"><v>a</v></c><c><v>1</v></c><c r="D1" t="b"><v>1</v></c></row>"#,
+        r#"<row spans="2:2"><c r="B2"><v>2</v></c></row>"#,
+        r#"<row r="4" spans="1:1"><c><v>3</v></c></row></sheetData>"#
+    )), "{}", xml);
+
+    let bytes = wb.to_bytes().unwrap();
+    let values = excel::Workbook::read_sheet_values(&bytes, "refs").unwrap();
+    assert_eq!(vec![vec!["a", "1", "", "TRUE"], vec!["", "2"], vec![], vec!["3"]], values);
+}
+
+#[test]
+fn writes_unpacked_parts() {
+    let dir = std::env::temp_dir().join("simple_excel_writer_unpacked");
+    let mut wb = excel::Workbook::create_in_memory();
+    let mut ws = wb.create_sheet("parts");
+    wb.write_sheet(&mut ws, |sw| sw.append_row(row!["a"])).expect("Write excel error!");
+    wb.write_unpacked(dir.to_str().unwrap()).expect("Write parts error!");
+
+    let sheet = std::fs::read_to_string(dir.join("xl/worksheets/sheet1.xml")).unwrap();
+    assert_eq!(wb.sheet_xml("parts").unwrap(), sheet);
+    let names = archive_file_names(&wb.to_bytes().unwrap());
+    for name in &names {
+        assert!(dir.join(name).is_file(), "{} is missing", name);
+    }
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn packs_a_directory_of_parts() {
+    let dir = std::env::temp_dir().join("simple_excel_writer_pack");
+    let out = std::env::temp_dir().join("simple_excel_writer_pack.xlsx");
+    let mut wb = excel::Workbook::create_in_memory();
+    let mut ws = wb.create_sheet("packed");
+    wb.write_sheet(&mut ws, |sw| sw.append_row(row!["a", 1.0])).expect("Write excel error!");
+    wb.write_unpacked(dir.to_str().unwrap()).expect("Write parts error!");
+
+    excel::Workbook::pack_dir(dir.to_str().unwrap(), out.to_str().unwrap()).expect("Pack error!");
+    let bytes = std::fs::read(&out).unwrap();
+    std::fs::remove_file(&out).unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    let mut names = archive_file_names(&bytes);
+    assert_eq!("[Content_Types].xml", names[0]);
+    let mut expected = archive_file_names(&wb.to_bytes().unwrap());
+    names.sort();
+    expected.sort();
+    assert_eq!(expected, names);
+    assert_eq!(vec![vec!["a", "1"]], excel::Workbook::read_sheet_values(&bytes, "packed").unwrap());
+}
+
+#[test]
+fn writes_currencies_with_their_formats() {
+    use excel::Currency;
+
+    let mut wb = excel::Workbook::create_in_memory();
+    let mut ws = wb.create_sheet("money");
+    wb.write_sheet(&mut ws, |sw| {
+        sw.append_row(row![Currency::usd(19.99), Currency::eur(17.5)])?;
+        sw.append_row(row![Currency::usd(5.0), Currency { amount: 12.0, code: "CHF" }, Currency::jpy(1500.0)])
+    })
+    .expect("Write excel error!");
+
+    let xml = wb.sheet_xml("money").unwrap();
+    assert!(xml.contains(r#"<c r="A1" s="3"><v>19.99</v></c><c r="B1" s="4"><v>17.5</v></c>"#), "{}", xml);
+    assert!(xml.contains(r#"<c r="A2" s="3"><v>5</v></c><c r="B2" s="5"><v>12</v></c><c r="C2" s="6"><v>1500</v></c>"#), "{}", xml);
+
+    let styles = get_file_as_str_from_zip(&wb.to_bytes().unwrap(), "xl/styles.xml");
+    assert!(styles.contains(r#"<numFmts count="4">"#), "{}", styles);
+    assert!(styles.contains(r##"formatCode="&quot;$&quot;#,##0.00""##), "{}", styles);
+    assert!(styles.contains(r##"formatCode="#,##0.00&quot; CHF&quot;""##), "{}", styles);
+}
+
+#[test]
+fn writes_iso_dates() {
+    let iso = |s: &str| CellValue::IsoDate(s.to_string());
+    let mut wb = excel::Workbook::create_in_memory();
+    let mut ws = wb.create_sheet("dates");
+    wb.write_sheet(&mut ws, |sw| {
+        sw.append_row(row![iso("2012-11-10"), iso("2012-11-10T15:17"), iso("2012-11-10T15:17:39.5Z")])
+    })
+    .expect("Write excel error!");
+
+    let xml = wb.sheet_xml("dates").unwrap();
+    assert!(xml.contains(r#"<c r="A1" s="1" t="d"><v>2012-11-10</v></c><c r="B1" s="2" t="d"><v>2012-11-10T15:17</v></c><c r="C1" s="2" t="d"><v>2012-11-10T15:17:39.5Z</v></c>"#), "{}", xml);
+
+    for invalid in &["2012-13-01", "12-11-10", "2012-11-10T25:00", "2012-11-10T15:17.5", "2012/11/10", "yesterday"] {
+        let mut ws = wb.create_sheet("invalid");
+        let result = wb.write_sheet(&mut ws, |sw| sw.append_row(row![iso(invalid)]));
+        assert_eq!(std::io::ErrorKind::InvalidInput, result.unwrap_err().kind(), "{}", invalid);
+    }
+
+    let mut ws = wb.create_sheet("partial");
+    wb.write_sheet(&mut ws, |sw| {
+        assert!(sw.append_row(row!["before", iso("2012-13-01")]).is_err());
+        sw.append_row(row!["after"])
+    })
+    .expect("Write excel error!");
+    let xml = wb.sheet_xml("partial").unwrap();
+    assert!(!xml.contains("before"), "{}", xml);
+    assert!(xml.contains(r#"<c r="A1" t="str"><v>after</v></c>"#), "{}", xml);
+}
+
+#[test]
+fn resets_workbooks_for_reuse() {
+    let path = std::env::temp_dir().join("simple_excel_writer_reset.xlsx");
+    let mut wb = excel::Workbook::create(path.to_str().unwrap());
+    let fmt = wb.add_cust_number_format("0.000".to_string());
+    let mut ws = wb.create_sheet("first");
+    wb.write_sheet(&mut ws, |sw| sw.append_row(row!["a", "b", (1.0, fmt)])).expect("Write excel error!");
+    wb.close().expect("Close excel error!");
+
+    wb.reset();
+    let mut ws = wb.create_sheet("second");
+    wb.write_sheet(&mut ws, |sw| sw.append_row(row!["c", (2.0, fmt)])).expect("Write excel error!");
+    wb.close().expect("Close excel error!");
+    let bytes = std::fs::read(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert!(wb.sheet_xml("first").is_none());
+    let workbook = get_file_as_str_from_zip(&bytes, "xl/workbook.xml");
+    assert!(workbook.contains(r#"<sheet name="second" sheetId="1" r:id="rId3"/>"#), "{}", workbook);
+    assert!(!workbook.contains("first"));
+    let shared = get_file_as_str_from_zip(&bytes, "xl/sharedStrings.xml");
+    assert!(shared.contains(r#"count="1" uniqueCount="1"><si><t>c</t></si>"#), "{}", shared);
+    assert_eq!(vec![vec!["c", "2"]], excel::Workbook::read_sheet_values(&bytes, "second").unwrap());
+    assert!(get_file_as_str_from_zip(&bytes, "xl/styles.xml").contains(r#"formatCode="0.000""#));
+}
+
+#[test]
+fn rejects_strings_longer_than_excel_allows() {
+    let mut wb = excel::Workbook::create_in_memory();
+    let mut ws = wb.create_sheet("long");
+    let longest = "x".repeat(excel::MAX_STRING_LEN);
+    let too_long = "é".repeat(excel::MAX_STRING_LEN + 1);
+    let result = wb.write_sheet(&mut ws, |sw| {
+        sw.append_row(row![longest.as_str()])?;
+        sw.append_row(row!["ok", too_long.as_str()])
+    });
+
+    let err = result.unwrap_err();
+    assert_eq!(std::io::ErrorKind::InvalidInput, err.kind());
+    assert!(err.to_string().contains("cell B2"), "{}", err);
+}
+
+#[test]
+fn makes_report_sheets() {
+    let mut wb = excel::Workbook::create_in_memory();
+    let mut ws = wb.create_sheet("plain");
+    wb.write_sheet(&mut ws, |sw| sw.append_row(row!["a"])).expect("Write excel error!");
+
+    let mut ws = wb.create_sheet("Bob's report");
+    ws.make_report(1);
+    wb.write_sheet(&mut ws, |sw| {
+        sw.append_row(row!["name", "qty", "price"])?;
+        sw.append_row(row!["apple", 3.0, 0.5])?;
+        sw.append_row(row!["pear", 1.0, 0.75])
+    }).expect("Write excel error!");
+
+    let xml = wb.sheet_xml("Bob's report").unwrap();
+    assert!(xml.contains(r#"<pane ySplit="1" topLeftCell="A2" activePane="bottomLeft" state="frozen"/>"#), "{}", xml);
+    assert!(xml.contains(r#"<autoFilter ref="A1:C3"/>"#), "{}", xml);
+    assert!(!wb.sheet_xml("plain").unwrap().contains("autoFilter"));
+
+    let mem_file = wb.close().expect("No error on workbook close!").expect("Should have file in memory!");
+    let workbook = get_file_as_str_from_zip(&mem_file, "xl/workbook.xml");
+    assert!(workbook.contains(r#"</sheets>
+    <definedNames><definedName name="_xlnm.Print_Titles" localSheetId="1">&apos;Bob&apos;&apos;s report&apos;!$1:$1</definedName></definedNames>"#), "{}", workbook);
+}
+
+#[test]
+fn writes_extension_lists() {
+    let ext = r#"<ext uri="{05C60535-1F16-4fd2-B633-F4F36F0B64E0}"><x14:sparklineGroups/></ext>"#;
+    let mut wb = excel::Workbook::create_in_memory();
+    wb.add_ext(r#"<ext uri="{7523E5D3-25F3-A5E0-1632-64F254C22452}"/>"#);
+    let mut ws = wb.create_sheet("trend");
+    ws.add_ext(ext);
+    ws.add_row_page_break(2);
+    wb.write_sheet(&mut ws, |sw| sw.append_row(row![1.0, 2.0, 3.0])).expect("Write excel error!");
+
+    let xml = wb.sheet_xml("trend").unwrap();
+    assert!(xml.contains(&format!("</rowBreaks><extLst>{}</extLst>\n</worksheet>", ext)), "{}", xml);
+
+    let mem_file = wb.close().expect("No error on workbook close!").expect("Should have file in memory!");
+    let workbook = get_file_as_str_from_zip(&mem_file, "xl/workbook.xml");
+    assert!(workbook.contains(r#"<extLst><ext uri="{7523E5D3-25F3-A5E0-1632-64F254C22452}"/></extLst>
+</workbook>"#), "{}", workbook);
+}
+
+#[test]
+fn adds_conditional_number_formats() {
+    let mut wb = excel::Workbook::create_in_memory();
+    let id = wb.add_conditional_number_format("#,##0.00", "[Red]-#,##0.00", "", "@").unwrap();
+    assert_eq!(3, id);
+    assert_eq!(4, wb.add_conditional_number_format("[Color10]0", "[<-100][Blue]\"[x]\"0", "[h]:mm", "[$-409]@").unwrap());
+
+    let error = wb.add_conditional_number_format("0", "[Rd]-0", "0", "@").unwrap_err();
+    assert_eq!(std::io::ErrorKind::InvalidInput, error.kind());
+    assert!(error.to_string().contains("unknown color [Rd]"), "{}", error);
+    assert!(wb.add_conditional_number_format("0;0", "-0", "0", "@").is_err());
+    assert!(wb.add_conditional_number_format("[Color57]0", "-0", "0", "@").is_err());
+
+    let mut ws = wb.create_sheet("colors");
+    wb.write_sheet(&mut ws, |sw| sw.append_row(row![(-1.5, id)])).expect("Write excel error!");
+    let mem_file = wb.close().expect("No error on workbook close!").expect("Should have file in memory!");
+    let styles = get_file_as_str_from_zip(&mem_file, "xl/styles.xml");
+    assert!(styles.contains(r##"<numFmt numFmtId="165" formatCode="#,##0.00;[Red]-#,##0.00;;@"/>"##), "{}", styles);
+}
+
+#[test]
+fn builds_bytes_on_another_thread() {
+    let mut wb = excel::Workbook::create("unused.xlsx");
+    wb.with_sheet_xml_transform(1, |xml| xml.extend_from_slice(b"<!-- transformed -->"));
+    let mut ws = wb.create_sheet("threads");
+    wb.write_sheet(&mut ws, |sw| sw.append_row(row!["moved"])).expect("Write excel error!");
+
+    let bytes = std::thread::spawn(move || wb.into_bytes()).join().unwrap().expect("Should build bytes!");
+    assert!(!std::path::Path::new("unused.xlsx").exists());
+    assert!(get_file_as_str_from_zip(&bytes, "xl/worksheets/sheet1.xml").ends_with("<!-- transformed -->"));
+    assert_eq!(vec![vec!["moved".to_string()]], excel::Workbook::read_sheet_values(&bytes, "threads").unwrap());
+}
+
+#[test]
+fn leaves_out_the_calc_chain() {
+    let mut wb = excel::Workbook::create_in_memory();
+    wb.without_calc_chain();
+    let mut ws = wb.create_sheet("formulas");
+    wb.write_sheet(&mut ws, |sw| sw.append_row(row![1.0, CellValue::Formula("A1*2".to_string())])).expect("Write excel error!");
+
+    let mem_file = wb.close().expect("No error on workbook close!").expect("Should have file in memory!");
+    let names = archive_file_names(&mem_file);
+    assert!(names.contains(&"xl/workbook.xml".to_string()));
+    assert!(!names.contains(&"xl/calcChain.xml".to_string()), "{:?}", names);
+    assert!(!get_file_as_str_from_zip(&mem_file, "[Content_Types].xml").contains("calcChain"));
+}
+
+#[test]
+fn appends_numeric_rows_with_one_format() {
+    let mut wb = excel::Workbook::create_in_memory();
+    let money = wb.add_grouped_number_format(2);
+    let mut ws = wb.create_sheet("numbers");
+    wb.write_sheet(&mut ws, |sw| {
+        sw.append_numeric_row(&[1234.5, -2.0, 0.25], money)?;
+        sw.append_numeric_row(&[], money)
+    }).expect("Write excel error!");
+
+    let xml = wb.sheet_xml("numbers").unwrap();
+    assert!(xml.contains(r#"<row r="1" spans="1:3">
+<c r="A1" s="3"><v>1234.5</v></c><c r="B1" s="3"><v>-2</v></c><c r="C1" s="3"><v>0.25</v></c>"#), "{}", xml);
+    assert_eq!(2, ws.row_count());
+}
+
+#[test]
+fn writes_numbers_as_shortest_round_tripping_decimals() {
+    let mut wb = excel::Workbook::create_in_memory();
+    let mut ws = wb.create_sheet("numbers");
+    wb.write_sheet(&mut ws, |sw| sw.append_row(row![0.1 + 0.2, 700.0, 25999.00, 0.1, -1.5e-7, 1e21])).expect("Write excel error!");
+
+    let xml = wb.sheet_xml("numbers").unwrap();
+    assert!(xml.contains(r#"<c r="A1"><v>0.30000000000000004</v></c>"#), "{}", xml);
+    assert!(xml.contains(r#"<c r="B1"><v>700</v></c>"#), "{}", xml);
+    assert!(xml.contains(r#"<c r="C1"><v>25999</v></c>"#), "{}", xml);
+    assert!(xml.contains(r#"<c r="D1"><v>0.1</v></c>"#), "{}", xml);
+    assert!(xml.contains(r#"<c r="E1"><v>-0.00000015</v></c>"#), "{}", xml);
+    assert!(xml.contains(r#"<c r="F1"><v>1000000000000000000000</v></c>"#), "{}", xml);
+
+    let mem_file = wb.close().expect("No error on workbook close!").expect("Should have file in memory!");
+    let values = excel::Workbook::read_sheet_values(&mem_file, "numbers").unwrap();
+    assert_eq!(0.1 + 0.2, values[0][0].parse::<f64>().unwrap());
+}
+
+#[test]
+fn shares_a_style_registry_between_workbooks() {
+    let mut registry = excel::StyleRegistry::new();
+    let money = registry.add_cust_number_format("#,##0.00 \"EUR\"".to_string());
+    let percent = registry.try_add_number_format("0.0%").unwrap();
+    assert!(registry.try_add_number_format("\"open").is_err());
+
+    for name in &["first", "second"] {
+        let path = std::env::temp_dir().join(format!("simple_excel_writer_registry_{}.xlsx", name));
+        {
+            let mut wb = excel::Workbook::create_with_styles(path.to_str().unwrap(), &registry);
+            assert_eq!(5, wb.add_cust_number_format("0.000".to_string()));
+            let mut ws = wb.create_sheet("styled");
+            wb.write_sheet(&mut ws, |sw| sw.append_row(row![(1.5, money), (0.25, percent)])).expect("Write excel error!");
+            wb.close().expect("Close excel error!");
+        }
+        let mem_file = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let styles = get_file_as_str_from_zip(&mem_file, "xl/styles.xml");
+        assert!(styles.contains(r##"<numFmt numFmtId="165" formatCode="#,##0.00 &quot;EUR&quot;"/>"##), "{}", styles);
+        assert!(styles.contains(r##"<numFmt numFmtId="166" formatCode="0.0%"/>"##), "{}", styles);
+        let sheet = get_file_as_str_from_zip(&mem_file, "xl/worksheets/sheet1.xml");
+        assert!(sheet.contains(r#"<c r="A1" s="3"><v>1.5</v></c><c r="B1" s="4"><v>0.25</v></c>"#), "{}", sheet);
+    }
+}
+
+#[test]
+fn writes_explicit_number_types() {
+    let mut wb = excel::Workbook::create_in_memory();
+    wb.explicit_number_type(true);
+    let mut ws = wb.create_sheet("typed");
+    wb.write_sheet(&mut ws, |sw| sw.append_row(row![1.5, (2.0, 1), "text"])).expect("Write excel error!");
+
+    let xml = wb.sheet_xml("typed").unwrap();
+    assert!(xml.contains(r#"<c r="A1" t="n"><v>1.5</v></c><c r="B1" s="1" t="n"><v>2</v></c><c r="C1" t="str">"#), "{}", xml);
+}
+
+#[test]
+fn styles_ranges_written_afterwards() {
+    let mut wb = excel::Workbook::create_in_memory();
+    let money = wb.add_grouped_number_format(2);
+    let mut ws = wb.create_sheet("ranges");
+    wb.write_sheet(&mut ws, |sw| {
+        sw.style_range("B2:C3", money)?;
+        sw.style_range("$C$3", 1)?;
+        sw.style_range("A2:A3", money)?;
+        assert!(sw.style_range("C3:B2", money).is_err());
+        assert!(sw.style_range("2B", money).is_err());
+        sw.append_row(row!["name", 1.0, 2.0])?;
+        sw.append_row(row!["a", 3.0, (4.0, 2)])?;
+        sw.append_row(row!["b", 5.0, 6.0])?;
+        sw.append_row(row!["c", 7.0, 8.0])
+    }).expect("Write excel error!");
+
+    let xml = wb.sheet_xml("ranges").unwrap();
+    assert!(xml.contains(r#"<c r="B1"><v>1</v></c>"#), "{}", xml);
+    assert!(xml.contains(r#"<c r="B2" s="3"><v>3</v></c><c r="C2" s="2"><v>4</v></c>"#), "{}", xml);
+    assert!(xml.contains(r#"<c r="B3" s="3"><v>5</v></c><c r="C3" s="1"><v>6</v></c>"#), "{}", xml);
+    assert!(xml.contains(r#"<c r="B4"><v>7</v></c>"#), "{}", xml);
+    assert!(xml.contains(r#"<c r="A2" t="str"><v>a</v></c>"#), "{}", xml);
+    assert!(xml.contains(r#"<c r="A3" t="str"><v>b</v></c>"#), "{}", xml);
+}
+
+#[test]
+fn adds_fraction_formats() {
+    let mut wb = excel::Workbook::create_in_memory();
+    assert_eq!(3, wb.add_fraction_format(1).unwrap());
+    assert_eq!(4, wb.add_fraction_format(2).unwrap());
+    assert_eq!(std::io::ErrorKind::InvalidInput, wb.add_fraction_format(0).unwrap_err().kind());
+    assert!(wb.add_fraction_format(4).is_err());
+    let mut ws = wb.create_sheet("fractions");
+    wb.write_sheet(&mut ws, |sw| sw.append_row(row![(3.5, 3), (3.390625, 4)])).expect("Write excel error!");
+
+    let mem_file = wb.close().expect("No error on workbook close!").expect("Should have file in memory!");
+    let styles = get_file_as_str_from_zip(&mem_file, "xl/styles.xml");
+    assert!(styles.contains(r##"<numFmt numFmtId="165" formatCode="# ?/?"/>"##), "{}", styles);
+    assert!(styles.contains(r##"<numFmt numFmtId="166" formatCode="# ??/??"/>"##), "{}", styles);
+}
+
+#[test]
+fn simple_workbooks_never_refer_to_shared_strings() {
+    let mut unused = excel::SharedStrings::new_unused();
+    match unused.register("a &amp; b") {
+        CellValue::String(s) => assert_eq!("a & b", s),
+        _ => panic!("unused shared strings should give inline strings"),
+    }
+    match unused.register_phonetic("漢字", "かんじ") {
+        CellValue::Phonetic { base, ruby } => assert_eq!(("漢字", "かんじ"), (base.as_str(), ruby.as_str())),
+        _ => panic!("unused shared strings should give inline strings"),
+    }
+
+    let path = std::env::temp_dir().join("simple_excel_writer_simple_strings.xlsx");
+    {
+        let mut wb = excel::Workbook::create_simple(path.to_str().unwrap());
+        let mut ws = wb.create_sheet("simple");
+        let registered = unused.register("registered");
+        wb.write_sheet(&mut ws, |sw| {
+            sw.append_row(row!["plain", registered.clone()])?;
+            let error = sw.append_row(row![CellValue::SharedString("0".to_string())]).unwrap_err();
+            assert_eq!(std::io::ErrorKind::InvalidInput, error.kind());
+            Ok(())
+        }).expect("Write excel error!");
+        assert!(!wb.sheet_xml("simple").unwrap().contains(r#"t="s""#));
+        wb.close().expect("Close excel error!");
+    }
+    let mem_file = std::fs::read(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert!(!archive_file_names(&mem_file).contains(&"xl/sharedStrings.xml".to_string()));
+    assert_eq!(vec![vec!["plain".to_string(), "registered".to_string()]],
+               excel::Workbook::read_sheet_values(&mem_file, "simple").unwrap());
+}
+
+#[test]
+fn writes_styled_blank_rows() {
+    let mut wb = excel::Workbook::create_in_memory();
+    let mut ws = wb.create_sheet("spacers");
+    wb.write_sheet(&mut ws, |sw| {
+        sw.append_row(row!["section 1"])?;
+        sw.append_styled_blank_rows(2, 3, 1)?;
+        sw.append_row(row!["section 2"])
+    }).expect("Write excel error!");
+
+    let xml = wb.sheet_xml("spacers").unwrap();
+    assert!(xml.contains(r#"<row r="2" spans="1:3">
+<c r="A2" s="1"/><c r="B2" s="1"/><c r="C2" s="1"/>"#), "{}", xml);
+    assert!(xml.contains(r#"<c r="C3" s="1"/>"#), "{}", xml);
+    assert!(xml.contains(r#"<c r="A4" t="str"><v>section 2</v></c>"#), "{}", xml);
+    assert_eq!(4, ws.row_count());
+}
+
+#[test]
+fn reports_progress_while_closing() {
+    let mut wb = excel::Workbook::create_in_memory();
+    for name in &["one", "two"] {
+        let mut ws = wb.create_sheet(name);
+        wb.write_sheet(&mut ws, |sw| sw.append_row(row!["a"])).expect("Write excel error!");
+    }
+
+    let mut events = Vec::new();
+    let mem_file = wb
+        .close_with_progress(|event| events.push(event))
+        .expect("No error on workbook close!")
+        .expect("Should have file in memory!");
+    let names = archive_file_names(&mem_file);
+    assert_eq!(excel::ProgressEvent::CreatingParts, events[0]);
+    assert_eq!(names.len() + 1, events.len());
+    assert_eq!(
+        excel::ProgressEvent::Zipping { part: 1, total: names.len(), name: "xl/worksheets/sheet1.xml".to_string() },
+        events[1]
+    );
+    assert!(!events.contains(&excel::ProgressEvent::Writing));
+}
+
+#[test]
+fn freezes_panes_scrolled_to_a_cell() {
+    let mut wb = excel::Workbook::create_in_memory();
+    let mut ws = wb.create_sheet("wide");
+    assert!(ws.freeze_panes_at(1, 2, "B5").is_err());
+    assert!(ws.freeze_panes_at(0, 0, "F3").is_err());
+    assert!(ws.freeze_panes_at(1, 2, "F").is_err());
+    ws.freeze_panes_at(1, 2, "$F$3").unwrap();
+    wb.write_sheet(&mut ws, |sw| sw.append_row(row!["a"])).expect("Write excel error!");
+
+    let xml = wb.sheet_xml("wide").unwrap();
+    assert!(xml.contains(r#"<pane xSplit="2" ySplit="1" topLeftCell="F3" activePane="bottomRight" state="frozen"/>"#), "{}", xml);
+}
+
+#[test]
+fn clears_the_report_auto_filter() {
+    let mut wb = excel::Workbook::create_in_memory();
+    let mut ws = wb.create_sheet("report");
+    ws.make_report(1);
+    ws.clear_auto_filter();
+    wb.write_sheet(&mut ws, |sw| {
+        sw.append_row(row!["name", "qty"])?;
+        sw.append_row(row!["apple", 3.0])
+    }).expect("Write excel error!");
+
+    let xml = wb.sheet_xml("report").unwrap();
+    assert!(!xml.contains("autoFilter"), "{}", xml);
+    assert!(xml.contains(r#"state="frozen""#), "{}", xml);
+}
+
+#[test]
+fn clears_an_added_auto_filter() {
+    let mut wb = excel::Workbook::create_in_memory();
+    let mut ws = wb.create_sheet("filtered");
+    ws.add_auto_filter(1, 4, 1, 21);
+    ws.clear_auto_filter();
+    assert!(ws.auto_filter.is_none(), "The autofilter was not removed!");
+    ws.clear_auto_filter();
+    assert!(ws.auto_filter.is_none());
+}
+
+#[test]
+fn later_auto_filters_replace_the_report_filter() {
+    let mut wb = excel::Workbook::create_in_memory();
+    let mut ws = wb.create_sheet("report");
+    ws.make_report(1);
+    ws.add_auto_filter(2, 2, 1, 10);
+    wb.write_sheet(&mut ws, |sw| sw.append_row(row!["name", "qty", "price"])).expect("Write excel error!");
+
+    let xml = wb.sheet_xml("report").unwrap();
+    assert!(xml.contains(r#"<autoFilter ref="B1:B10"/>"#), "{}", xml);
+}
+
+#[test]
+fn writes_sheets_owned_by_the_workbook() {
+    let mut wb = excel::Workbook::create_in_memory();
+    let summary = wb.add_sheet("summary");
+    let data = wb.add_sheet("data");
+    wb.sheet_mut(data).unwrap().freeze_panes(1, 0);
+
+    wb.write_sheet_by_id(data, |sw| sw.append_row(row!["value"])).expect("Write excel error!");
+    assert!(wb.sheet_mut(data).is_none());
+    let error = wb.write_sheet_by_id(data, |sw| sw.append_row(row!["again"])).unwrap_err();
+    assert_eq!(std::io::ErrorKind::NotFound, error.kind());
+    assert!(wb.sheet_xml("data").unwrap().contains(r#"state="frozen""#));
+
+    let error = wb.to_bytes().unwrap_err();
+    assert!(error.to_string().contains("sheet summary was added but never written"), "{}", error);
+    wb.write_sheet_by_id(summary, |sw| sw.append_row(row!["total"])).expect("Write excel error!");
+
+    let mem_file = wb.close().expect("No error on workbook close!").expect("Should have file in memory!");
+    let workbook = get_file_as_str_from_zip(&mem_file, "xl/workbook.xml");
+    assert!(workbook.contains(r#"<sheet name="summary" sheetId="1" r:id="rId3"/><sheet name="data" sheetId="2" r:id="rId4"/>"#), "{}", workbook);
+}
+
+#[test]
+fn column_styles_leave_header_formats_alone() {
+    let mut wb = excel::Workbook::create_in_memory();
+    let money = wb.add_grouped_number_format(2);
+    let mut ws = wb.create_sheet("styled columns");
+    ws.set_column_widths(&[20.0]);
+    ws.set_column_style(3, money);
+    ws.add_auto_filter(1, 3, 1, 3);
+    wb.write_sheet(&mut ws, |sw| {
+        sw.append_row(row!["name", "year", (2024.0, 0)])?;
+        sw.append_row(row!["apple", 2023.0, 1234.5])?;
+        sw.append_row(row!["pear", 2024.0, (0.5, 1)])
+    }).expect("Write excel error!");
+
+    let xml = wb.sheet_xml("styled columns").unwrap();
+    assert!(xml.contains(r#"<cols>
+<col min="1" max="1" width="20" customWidth="1"/>
+<col min="3" max="3" style="3"/>
+</cols>"#), "{}", xml);
+    assert!(xml.contains(r#"<c r="C1" s="0"><v>2024</v></c>"#), "{}", xml);
+    assert!(xml.contains(r#"<c r="B2"><v>2023</v></c><c r="C2" s="3"><v>1234.5</v></c>"#), "{}", xml);
+    assert!(xml.contains(r#"<c r="C3" s="1"><v>0.5</v></c>"#), "{}", xml);
+}
+
+#[test]
+fn prefixes_parts_with_a_bom() {
+    let mut wb = excel::Workbook::create_in_memory();
+    wb.with_bom(true);
+    let mut ws = wb.create_sheet("bom");
+    wb.write_sheet(&mut ws, |sw| sw.append_row(row!["a"])).expect("Write excel error!");
+
+    let mem_file = wb.close().expect("No error on workbook close!").expect("Should have file in memory!");
+    for name in archive_file_names(&mem_file) {
+        assert!(get_file_as_str_from_zip(&mem_file, &name).starts_with("\u{FEFF}<?xml"), "{}", name);
+    }
+    assert_eq!(vec![vec!["a".to_string()]], excel::Workbook::read_sheet_values(&mem_file, "bom").unwrap());
+
+    let mut wb = excel::Workbook::create_in_memory();
+    let mut ws = wb.create_sheet("no bom");
+    wb.write_sheet(&mut ws, |sw| sw.append_row(row!["a"])).expect("Write excel error!");
+    let mem_file = wb.close().expect("No error on workbook close!").expect("Should have file in memory!");
+    assert!(get_file_as_str_from_zip(&mem_file, "xl/workbook.xml").starts_with("<?xml"));
+}
+
+#[test]
+fn adds_percent_display_format() {
+    let mut wb = excel::Workbook::create_in_memory();
+    let percent = wb.add_percent_display_format();
+    let mut ws = wb.create_sheet("percentages");
+    wb.write_sheet(&mut ws, |sw| sw.append_row(row![(25.0, percent)])).expect("Write excel error!");
+
+    assert!(wb.sheet_xml("percentages").unwrap().contains(r#"<c r="A1" s="3"><v>25</v></c>"#));
+    let mem_file = wb.close().expect("No error on workbook close!").expect("Should have file in memory!");
+    let styles = get_file_as_str_from_zip(&mem_file, "xl/styles.xml");
+    assert!(styles.contains(r#"<numFmt numFmtId="165" formatCode="0.00&quot;%&quot;"/>"#), "{}", styles);
+}
+
+#[test]
+fn writes_unformatted_numbers_with_the_default_format() {
+    let mut wb = excel::Workbook::create_in_memory();
+    let integer = wb.add_cust_number_format("0".to_string());
+    wb.set_default_number_format(integer);
+    let mut ws = wb.create_sheet("ids");
+    wb.write_sheet(&mut ws, |sw| sw.append_row(row![1234567890123456.0, (0.5, 1), "text"])).expect("Write excel error!");
+
+    let xml = wb.sheet_xml("ids").unwrap();
+    assert!(xml.contains(r#"<c r="A1" s="3"><v>1234567890123456</v></c><c r="B1" s="1"><v>0.5</v></c><c r="C1" t="str">"#), "{}", xml);
+}
+
+#[test]
+fn detects_numbers_in_data_sheets() {
+    for (value, expected) in &[("42", true), ("-0.5", true), ("3.14", true), ("0", true), ("007", false),
+                               ("+1", false), ("1e5", false), ("1,000", false), (" 1", false), ("1.", false), ("-", false)] {
+        assert_eq!(*expected, excel::looks_like_number(value), "{}", value);
+    }
+
+    let mut wb = excel::Workbook::create_in_memory();
+    wb.add_data_sheet_with("default", &[&["0612345678", "12.5", "NaN"]], excel::looks_like_number).unwrap();
+    wb.add_data_sheet_with("custom", &[&["0612345678", "1e3", "inf"]], |_| true).unwrap();
+
+    let xml = wb.sheet_xml("default").unwrap();
+    assert!(xml.contains(r#"<c r="A1" t="str"><v>0612345678</v></c><c r="B1"><v>12.5</v></c><c r="C1" t="str"><v>NaN</v></c>"#), "{}", xml);
+    let xml = wb.sheet_xml("custom").unwrap();
+    assert!(xml.contains(r#"<c r="A1"><v>612345678</v></c><c r="B1"><v>1000</v></c><c r="C1" t="str"><v>inf</v></c>"#), "{}", xml);
+}
+
+#[test]
+fn writes_rows_with_a_default_style() {
+    let mut wb = excel::Workbook::create_in_memory();
+    let money = wb.add_grouped_number_format(2);
+    let mut ws = wb.create_sheet("banded");
+    ws.set_column_style(2, 1);
+    wb.write_sheet(&mut ws, |sw| {
+        sw.append_row_with_style(row!["group", 1.5, (2.5, 2)], money)?;
+        sw.append_row(row!["item", 3.5])
+    }).expect("Write excel error!");
+
+    let xml = wb.sheet_xml("banded").unwrap();
+    assert!(xml.contains(r#"<row r="1" spans="1:3" s="3" customFormat="1">
+<c r="A1" t="str"><v>group</v></c><c r="B1" s="3"><v>1.5</v></c><c r="C1" s="2"><v>2.5</v></c>"#), "{}", xml);
+    assert!(xml.contains(r#"<row r="2" spans="1:2">
+<c r="A2" t="str"><v>item</v></c><c r="B2" s="1"><v>3.5</v></c>"#), "{}", xml);
+}
+
+#[test]
+fn returns_the_used_range() {
+    let mut wb = excel::Workbook::create_in_memory();
+    let mut ws = wb.create_sheet("range");
+    assert_eq!(None, ws.used_range());
+    wb.write_sheet(&mut ws, |sw| {
+        sw.append_row(row!["a", "b"])?;
+        sw.append_blank_rows(98);
+        sw.append_row(row![blank!(3), "d"])
+    }).expect("Write excel error!");
+    assert_eq!(Some("A1:D100".to_string()), ws.used_range());
+
+    let mut ws = wb.create_sheet("blank rows only");
+    wb.write_sheet(&mut ws, |sw| {
+        sw.append_blank_rows(3);
+        Ok(())
+    }).expect("Write excel error!");
+    assert_eq!(None, ws.used_range());
+}
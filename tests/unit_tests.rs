@@ -1,3 +1,4 @@
+#[macro_use]
 extern crate simple_excel_writer;
 
 use simple_excel_writer as excel;
@@ -28,3 +29,158 @@ fn adds_autofilter_to_worksheet() {
 
     _ = wb.close();
 }
+
+#[test]
+fn converts_column_widths_from_pixels_and_chars() {
+    assert_eq!(9.140625, excel::Column::from_pixels(64).width);
+    assert_eq!(8.7109375, excel::Column::from_chars(8).width);
+    assert_eq!(excel::Column::from_pixels(61).width, excel::Column::from_chars(8).width);
+    assert_eq!(0.0, excel::Column::from_pixels(0).width);
+}
+
+#[test]
+fn sets_column_widths() {
+    let mut wb = excel::Workbook::create_in_memory();
+    let mut ws = wb.create_sheet("test_sheet");
+    ws.add_column(excel::Column { width: 5.0 });
+
+    ws.set_column_widths(&[10.0, 20.0, 30.0]);
+    let widths: Vec<f32> = ws.columns.iter().map(|c| c.width).collect();
+    assert_eq!(vec![10.0, 20.0, 30.0], widths);
+
+    ws.set_column_width(2, 25.0);
+    ws.set_column_width(0, 99.0);
+    ws.set_column_width(4, 99.0);
+    let widths: Vec<f32> = ws.columns.iter().map(|c| c.width).collect();
+    assert_eq!(vec![10.0, 25.0, 30.0], widths);
+}
+
+#[test]
+fn writes_128_bit_integers_as_text() {
+    use excel::{CellValue, ToCellValue};
+
+    match u128::MAX.to_cell_value() {
+        CellValue::String(s) => assert_eq!("340282366920938463463374607431768211455", s),
+        _ => panic!("u128 should become a string cell"),
+    }
+    match (-9_007_199_254_740_993i128).to_cell_value() {
+        CellValue::String(s) => assert_eq!("-9007199254740993", s),
+        _ => panic!("i128 should become a string cell"),
+    }
+}
+
+#[test]
+fn validates_sheet_names() {
+    for forbidden in &["\\", "/", "?", "*", "[", "]", ":"] {
+        assert_eq!("a-b", excel::validate_name(&format!("a{}b", forbidden)), "{} should be replaced", forbidden);
+    }
+    assert_eq!("ab", excel::validate_name("a\u{0}b"));
+    assert_eq!("ab", excel::validate_name("a\tb"));
+    assert_eq!("ab", excel::validate_name("a\nb"));
+    assert_eq!("ab", excel::validate_name("a\u{7f}b"));
+    assert_eq!("Bob&apos;s", excel::validate_name("'Bob's'"));
+    assert_eq!("History-", excel::validate_name("History"));
+    assert_eq!("history-", excel::validate_name("history"));
+    assert_eq!("History 2020", excel::validate_name("History 2020"));
+    assert_eq!("a&lt;b", excel::validate_name("a<b"));
+}
+
+#[test]
+fn names_sheets_with_nothing_left_of_their_name_by_id() {
+    for empty in &["", "''", "'\u{7}'", "\u{1}\t\n"] {
+        assert_eq!("", excel::validate_name(empty), "{:?}", empty);
+        assert_eq!("Sheet3", excel::Sheet::new(3, empty).name, "{:?}", empty);
+    }
+    let mut wb = excel::Workbook::create_in_memory();
+    wb.create_sheet("data");
+    assert_eq!("Sheet2", wb.create_sheet("''").name);
+}
+
+#[test]
+fn validates_number_formats() {
+    let mut wb = excel::Workbook::create_in_memory();
+    for valid in &[
+        "0.00",
+        "\"€\"#,##0.00",
+        "#,##0.00;[Red]-#,##0.00;0;@",
+        "[$-409]mmmm d, yyyy",
+        "0\\ \"x\"",
+        "_(* #,##0_)",
+        "\"a;b\"0",
+    ] {
+        assert!(wb.try_add_number_format(valid).is_ok(), "{} should be valid", valid);
+    }
+    for invalid in &["", "\"€#,##0.00", "[Red0.00", "0.00]", "0;0;0;@;0", "0.00\\", "0_", "[[Red]]0"] {
+        assert!(wb.try_add_number_format(invalid).is_err(), "{} should be invalid", invalid);
+    }
+}
+
+#[test]
+fn converts_column_indexes_to_letters() {
+    let cases = [
+        (1, "A"),
+        (2, "B"),
+        (26, "Z"),
+        (27, "AA"),
+        (52, "AZ"),
+        (53, "BA"),
+        (702, "ZZ"),
+        (703, "AAA"),
+        (16384, "XFD"),
+    ];
+    for &(index, letters) in cases.iter() {
+        assert_eq!(letters, excel::column_letter(index), "column {}", index);
+    }
+    assert_eq!("AB3", excel::ref_id(28, 3));
+}
+
+#[test]
+#[should_panic]
+fn column_letter_rejects_zero() {
+    excel::column_letter(0);
+}
+
+#[test]
+fn writes_chars_as_strings() {
+    use excel::{CellValue, ToCellValue};
+
+    for c in &['Y', '=', 'é'] {
+        match c.to_cell_value() {
+            CellValue::String(s) => assert_eq!(c.to_string(), s),
+            _ => panic!("char should become a string cell"),
+        }
+    }
+}
+
+#[test]
+fn join_keeps_the_joined_row_layout() {
+    use excel::{CellValue, Row};
+
+    let mut row = row!["a", "b"];
+    row.join(row![blank!(2), "c", (), "d", blank!(1)]);
+    row.add_cell("e");
+
+    let columns: Vec<usize> = row.cells.iter().map(|c| c.column_index).collect();
+    assert_eq!(vec![1, 2, 5, 7, 9], columns);
+}
+
+#[test]
+fn converts_borrowed_strings() {
+    use excel::{CellValue, Row};
+
+    let owned: Vec<String> = ["name", "=A1"].iter().map(|s| s.to_string()).collect();
+    let borrowed = ["x", "y"];
+    let mut row = Row::from_iter(owned.iter());
+    row.join(Row::from_iter(borrowed.iter()));
+
+    let values: Vec<String> = row
+        .cells
+        .iter()
+        .map(|c| match &c.value {
+            CellValue::String(s) => s.to_owned(),
+            CellValue::Formula(f) => format!("formula {}", f),
+            _ => panic!("borrowed strings should become strings or formulas"),
+        })
+        .collect();
+    assert_eq!(vec!["name", "formula =A1", "x", "y"], values);
+}